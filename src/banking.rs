@@ -0,0 +1,149 @@
+// This module decides in which ROM banks the generated assets are placed. Assets in switchable banks are
+// annotated for GBDK: the C file receives a "#pragma bank" and a BANKREF symbol, whose BANKREF_EXTERN
+// declaration in the header allows code in other banks to switch to the bank of the asset. Assets without a
+// specified bank can be packed into a list of available banks.
+
+use clap::ValueEnum;
+
+/// Size of a switchable ROM bank (0x4000-0x7FFF)
+pub const ROM_BANK_SIZE: usize = 0x4000;
+
+// GBDK's bank number that lets the linker (bankpack) choose the bank
+const GBDK_AUTOBANK_NUMBER: u32 = 255;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RomBank {
+    Fixed(u32),
+    // The bank is chosen by GBDK's bankpack during linking
+    Autobank,
+}
+
+impl RomBank {
+    /// The bank number of "#pragma bank"
+    pub fn pragma_bank_number(&self) -> u32 {
+        match self {
+            RomBank::Fixed(bank) => *bank,
+            RomBank::Autobank => GBDK_AUTOBANK_NUMBER,
+        }
+    }
+}
+
+/// The ROM bank of an asset, the asset is identified by the name of its output file without extension
+#[derive(Clone)]
+pub struct AssetRomBank {
+    pub asset_name: String,
+    pub rom_bank: RomBank,
+}
+
+/// Parses an asset ROM bank of the form asset_name=bank (e.g. tileset=2 or level1=autobank)
+pub fn parse_asset_rom_bank(str: &str) -> Result<AssetRomBank, String> {
+    let Some((asset_name, bank_str)) = str.split_once('=') else {
+        return Err(String::from("expected the form asset_name=bank"));
+    };
+    let rom_bank = match bank_str {
+        "autobank" => RomBank::Autobank,
+        _ => match bank_str.parse::<u32>() {
+            // Bank 0 is the fixed bank, that doesn't need any annotations
+            Ok(bank) if bank >= 1 && bank < GBDK_AUTOBANK_NUMBER => RomBank::Fixed(bank),
+            _ => return Err(format!("\"{}\" is neither a bank between 1 and {} nor \"autobank\"", bank_str, GBDK_AUTOBANK_NUMBER - 1)),
+        },
+    };
+    return Ok(AssetRomBank {
+        asset_name: asset_name.to_string(),
+        rom_bank: rom_bank,
+    });
+}
+
+/// A switchable ROM bank that the bank packer may fill
+#[derive(Clone)]
+pub struct AvailableRomBank {
+    pub bank: u32,
+    pub free_space: usize,
+}
+
+/// Parses an available ROM bank of the form bank=free_bytes or bank (a completely free bank)
+pub fn parse_available_rom_bank(str: &str) -> Result<AvailableRomBank, String> {
+    let (bank_str, free_space_str) = match str.split_once('=') {
+        Some((bank_str, free_space_str)) => (bank_str, Some(free_space_str)),
+        None => (str, None),
+    };
+    let bank = match bank_str.parse::<u32>() {
+        Ok(bank) if bank >= 1 && bank < GBDK_AUTOBANK_NUMBER => bank,
+        _ => return Err(format!("\"{}\" is not a bank between 1 and {}", bank_str, GBDK_AUTOBANK_NUMBER - 1)),
+    };
+    let free_space = match free_space_str {
+        Some(free_space_str) => match free_space_str.parse::<usize>() {
+            Ok(free_space) if free_space <= ROM_BANK_SIZE => free_space,
+            _ => return Err(format!("\"{}\" is not a number of bytes between 0 and {}", free_space_str, ROM_BANK_SIZE)),
+        },
+        None => ROM_BANK_SIZE,
+    };
+    return Ok(AvailableRomBank {
+        bank: bank,
+        free_space: free_space,
+    });
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BankPackingStrategy {
+    /// Every asset is placed in the first bank with enough free space
+    #[value(name = "first_fit")]
+    FirstFit,
+    /// Every asset is placed in the bank with the least free space that is still enough
+    #[value(name = "best_fit")]
+    BestFit,
+}
+
+/// Assigns assets (name and size) to the available banks and reduces their free space accordingly. The assets
+/// are placed from the biggest to the smallest one, since big assets are the hardest to place.
+pub fn pack_assets_into_banks(assets: &Vec<(String, usize)>, available_rom_banks: &mut Vec<AvailableRomBank>, bank_packing_strategy: BankPackingStrategy) -> Vec<(String, u32)> {
+    let mut sorted_assets: Vec<&(String, usize)> = assets.iter().collect();
+    sorted_assets.sort_by(|x, y| y.1.cmp(&x.1));
+
+    let mut res = Vec::new();
+    for (asset_name, asset_size) in sorted_assets {
+        let fitting_banks = available_rom_banks.iter_mut().filter(|x| x.free_space >= *asset_size);
+        let chosen_bank = match bank_packing_strategy {
+            BankPackingStrategy::FirstFit => fitting_banks.into_iter().next(),
+            // min_by_key returns the first of equally good banks
+            BankPackingStrategy::BestFit => fitting_banks.min_by_key(|x| x.free_space),
+        };
+        let Some(chosen_bank) = chosen_bank else {
+            panic!(
+                "The asset \"{}\" ({} bytes) doesn't fit into any of the available ROM banks (free space: {})",
+                asset_name, asset_size,
+                available_rom_banks.iter().map(|x| format!("bank {}: {} bytes", x.bank, x.free_space)).collect::<Vec<String>>().join(", ")
+            );
+        };
+        chosen_bank.free_space -= asset_size;
+        res.push((asset_name.clone(), chosen_bank.bank));
+    }
+
+    return res;
+}
+
+/// Splits data of data_size bytes into parts that fill the available banks in the given order. Parts only
+/// consist of whole units (e.g. tiles of 16 bytes). Returns the bank and the number of units of every part.
+pub fn split_data_across_banks(data_size: usize, unit_size: usize, available_rom_banks: &mut Vec<AvailableRomBank>) -> Vec<(u32, usize)> {
+    let mut res = Vec::new();
+    let mut remaining_units = data_size / unit_size;
+
+    for available_rom_bank in available_rom_banks.iter_mut() {
+        if remaining_units == 0 {
+            break;
+        }
+        let part_units = remaining_units.min(available_rom_bank.free_space / unit_size);
+        if part_units == 0 {
+            continue;
+        }
+        available_rom_bank.free_space -= part_units * unit_size;
+        remaining_units -= part_units;
+        res.push((available_rom_bank.bank, part_units));
+    }
+
+    if remaining_units > 0 {
+        panic!("{} bytes of data don't fit into the available ROM banks, even if the data is split across them", data_size);
+    }
+
+    return res;
+}
@@ -1,5 +1,14 @@
 use std::path::PathBuf;
 use clap::Parser;
+use image::Rgb;
+
+use crate::compression::{CompressionFormat, MapCompressionChunking};
+use crate::collision::{CollisionColor, parse_collision_color};
+use crate::dmg_palette::{DmgShadeColor, parse_dmg_shade_color};
+use crate::read_input::parse_rgb_color;
+use crate::quantization::{ColorDistanceMetric, Dithering};
+use crate::color_matching::{ColorAlias, parse_color_alias};
+use crate::banking::{AssetRomBank, AvailableRomBank, BankPackingStrategy, parse_asset_rom_bank, parse_available_rom_bank};
 
 #[derive(Parser)]
 #[command(name = "ITGBA")]
@@ -27,6 +36,33 @@ pub struct Cli_parser {
     #[arg(long = "mwa", long = "gbc_map_with_attributes", value_name = "path_list", num_args=1..)]
     pub map_with_attributes_file_paths: Vec<PathBuf>,
 
+    /// Supply maps created with the Tiled map editor (.tmx or .tmj/.json files, with a tile layer
+    /// format of CSV). Instead of comparing pixels, the tile ids stored by Tiled are translated directly
+    /// to tile indices, so the tilesets used in the map need to use the reference tileset image
+    /// (which therefore needs to be supplied as a single image). Every tile layer of the map
+    /// (e.g. background, window, collision) receives its own tile index array.
+    /// Like with the -m/--map option, flipped tiles aren't allowed.
+    #[arg(long = "tiled_map", value_name = "path_list", num_args=1..)]
+    pub tiled_map_file_paths: Vec<PathBuf>,
+
+    /// Same as --tiled_map, but Tiled's flip flags are allowed and translated into an additional
+    /// GBC background-attributes array for every tile layer.
+    #[arg(long = "tiled_mwa", visible_alias = "tiled_map_with_attributes", value_name = "path_list", num_args=1..)]
+    pub tiled_map_with_attributes_file_paths: Vec<PathBuf>,
+
+    /// Supply LDtk projects (.ldtk files). Every level of a project receives its own output file
+    /// that contains a tile index array for every tile layer (and auto layer), the values of every
+    /// IntGrid layer and the positions of the entities as an array of C structs. Like with Tiled maps,
+    /// the tileset used in LDtk needs to be the reference tileset image.
+    /// Flipped tiles aren't allowed.
+    #[arg(long = "ldtk", value_name = "path_list", num_args=1..)]
+    pub ldtk_project_file_paths: Vec<PathBuf>,
+
+    /// Same as --ldtk, but flipped tiles are allowed and translated into an additional
+    /// GBC background-attributes array for every tile layer.
+    #[arg(long = "ldtk_mwa", visible_alias = "ldtk_project_with_attributes", value_name = "path_list", num_args=1..)]
+    pub ldtk_project_with_attributes_file_paths: Vec<PathBuf>,
+
     /// Supply the tileset either via a directory that contains a separate image for each 8x8 tile
     /// or via a single image that contains all of the tiles. In the former case, a directory path should
     /// be supplied and the tiles will be ordered lexicographically (in addition to the assigned tile id,
@@ -42,6 +78,223 @@ pub struct Cli_parser {
     #[arg(short = 'r', long = "reference_tileset", value_name = "directory_path or file_path", required = true)]
     pub reference_tileset_path: PathBuf,
 
+    /// Aseprite files (.aseprite/.ase) can be used in place of any image. By default every visible
+    /// layer is merged. Use this option to merge only the layers (or groups) with the given names
+    /// instead, regardless of their visibility. The frames of an Aseprite file are placed next to each
+    /// other from left-to-right, slices name the tiles they cover and tags (of a tileset) generate
+    /// constants that describe the tile index range of the animation.
+    #[arg(long = "aseprite_layers", value_name = "layer_name_list", num_args=1.., conflicts_with = "aseprite_skip_layers")]
+    pub aseprite_layers: Vec<String>,
+
+    /// Merge every visible layer of Aseprite files, except for the layers (or groups) with the given names.
+    #[arg(long = "aseprite_skip_layers", value_name = "layer_name_list", num_args=1..)]
+    pub aseprite_skip_layers: Vec<String>,
+
+    /// Use this flag to read the palette indices of indexed (palette based) PNG images directly
+    /// instead of matching their colors against the colors of the reference tile. The palette indices
+    /// 0-3 are used as the 2bpp values. If the reference tileset is made of indexed images, it doesn't
+    /// need to contain a reference tile (the first four colors of its palette are used to match the
+    /// colors of non indexed images).
+    #[arg(long = "use_palette_indices", value_name = "bool", default_value_t = false)]
+    pub use_palette_indices: bool,
+
+    /// Use this flag if the reference tileset doesn't contain a reference tile. The palette is made of the
+    /// (at most 4) colors used in the tileset, sorted from the lightest to the darkest one.
+    #[arg(long = "infer_palette")]
+    pub infer_palette: bool,
+
+    /// The order of the colors of an inferred palette, e.g. --palette_order ffffff ff0000 800000 000000.
+    /// Every color used in the tileset needs to be listed.
+    #[arg(long = "palette_order", value_name = "RRGGBB list", num_args=1.., value_parser = parse_rgb_color, requires = "infer_palette")]
+    pub palette_order: Vec<Rgb<u8>>,
+
+    /// Colors whose channels (red, green and blue) each differ by at most this value from a palette color
+    /// are treated as the palette color. Helps with editors that shift colors slightly when exporting.
+    /// Can't be combined with --infer_palette, since the palette colors have to be known before matching.
+    #[arg(long = "color_tolerance", value_name = "tolerance", default_value_t = 0, conflicts_with = "infer_palette")]
+    pub color_tolerance: u8,
+
+    /// Colors that stand for a palette index, in the form RRGGBB=palette_index (e.g. --color_aliases
+    /// 0f380f=3 306230=2). The images that use every alias are printed. Can't be combined with --infer_palette.
+    #[arg(long = "color_aliases", value_name = "RRGGBB=palette_index list", num_args=1.., value_parser = parse_color_alias, conflicts_with = "infer_palette")]
+    pub color_aliases: Vec<ColorAlias>,
+
+    /// Quantize colors of the tileset and the map images, that aren't part of the palette, to the nearest
+    /// palette color instead of failing. This allows using painted or photographic art. The number of
+    /// altered pixels of every image is printed.
+    #[arg(long = "quantize", conflicts_with = "infer_palette")]
+    pub quantize: bool,
+
+    /// The metric that decides which palette color is the nearest one when quantizing
+    #[arg(long = "color_distance", value_name = "metric", value_enum, default_value_t = ColorDistanceMetric::Euclidean, requires = "quantize")]
+    pub color_distance_metric: ColorDistanceMetric,
+
+    /// Dither quantized colors. Ordered dithering treats equal tiles equally, so tiles of maps still match
+    /// the tiles of the tileset, whereas Floyd-Steinberg dithering depends on the surrounding pixels.
+    #[arg(long = "dither", value_name = "dithering", value_enum, default_value_t = Dithering::None, requires = "quantize")]
+    pub dithering: Dithering,
+
+    /// Compress the tile data of the tileset. Constants for the compressed and uncompressed size
+    /// are generated alongside the compressed data.
+    #[arg(long = "compress_tiles", value_name = "format", value_enum, default_value_t = CompressionFormat::None)]
+    pub tile_data_compression: CompressionFormat,
+
+    /// Compress the byte arrays of maps (tile indices, attributes, ...). gbcompress is LZ-style
+    /// (it can copy previous parts of the data), which pays off for maps with repeated patterns.
+    /// The compressed and uncompressed size of every map file is listed by --report.
+    #[arg(long = "compress_maps", value_name = "format", value_enum, default_value_t = CompressionFormat::None)]
+    pub map_compression: CompressionFormat,
+
+    /// Compress maps as a whole or every row or column separately. In the latter case an additional
+    /// array with the offsets of the compressed rows/columns is generated, so that a scrolling engine
+    /// can decompress single rows/columns.
+    #[arg(long = "map_compression_chunks", value_name = "chunking", value_enum, default_value_t = MapCompressionChunking::Whole)]
+    pub map_compression_chunking: MapCompressionChunking,
+
+    /// Group the tiles of every map into NxN blocks (metatiles), e.g. 2 for 16x16 pixel blocks.
+    /// Instead of the tile index and attribute arrays, a table with the tile indices (and attributes)
+    /// of every distinct metatile and a map of metatile indices are generated.
+    #[arg(long = "metatile_size", value_name = "N")]
+    pub metatile_size: Option<u32>,
+
+    /// Colors of collision images and the collision values they stand for, in the form RRGGBB=value
+    /// (e.g. --collision_colors 000000=1 0000ff=2 for solid and water). If specified, every map supplied
+    /// via -m/--map or --mwa can have a companion collision image with the same dimensions
+    /// (e.g. "level_collision.png" for "level.png"), that generates a collision array aligned with the
+    /// tile index array (or the metatile map). Every tile receives the value of the color that covers most
+    /// of its pixels, colors that aren't listed stand for the value 0.
+    #[arg(long = "collision_colors", value_name = "RRGGBB=value list", num_args=1.., value_parser = parse_collision_color)]
+    pub collision_colors: Vec<CollisionColor>,
+
+    /// Suffix of the file names of collision images, that is appended to the file stem of the map
+    #[arg(long = "collision_suffix", value_name = "suffix", default_value = "_collision")]
+    pub collision_image_suffix: String,
+
+    /// Path of a tile properties file, that assigns properties (e.g. solid, damage, animated or custom flags)
+    /// to tiles of the reference tileset. Every line has the form "<tile name or tile index>: <property> <property> ...".
+    /// Tiles of a reference tileset directory also receive the names of the subdirectories they are in as properties.
+    /// If any tile has properties, a property byte per tile is generated (see the README for the bits)
+    #[arg(long = "tile_properties", value_name = "file_path")]
+    pub tile_properties_file_path: Option<PathBuf>,
+
+    /// Places assets in switchable ROM banks, in the form asset_name=bank (e.g. --bank tileset=2 level1=autobank).
+    /// Assets are identified by the name of their output file without extension. The C file of a banked asset
+    /// receives a "#pragma bank" (255 for autobank) and a BANKREF symbol, and a header file with the matching
+    /// BANKREF_EXTERN declaration is generated.
+    #[arg(long = "bank", value_name = "asset_name=bank list", num_args=1.., value_parser = parse_asset_rom_bank)]
+    pub asset_rom_banks: Vec<AssetRomBank>,
+
+    /// Packs all assets without a bank specified via --bank into these ROM banks, in the form bank=free_bytes
+    /// or bank for a completely free bank (e.g. --pack_banks 1 2 3=4096). The resulting bank map is printed and
+    /// written to bank_map.txt
+    #[arg(long = "pack_banks", value_name = "bank=free_bytes list", num_args=1.., value_parser = parse_available_rom_bank)]
+    pub available_rom_banks: Vec<AvailableRomBank>,
+
+    /// How the bank packer chooses the bank of an asset
+    #[arg(long = "bank_packing", value_name = "strategy", value_enum, default_value_t = BankPackingStrategy::FirstFit)]
+    pub bank_packing_strategy: BankPackingStrategy,
+
+    /// Use this flag if the tile data may be split across the banks of --pack_banks, if it doesn't fit into a
+    /// single one. Every part becomes a separate output file "<tileset>_part<n>" (requires uncompressed tile data)
+    #[arg(long = "split_tile_data")]
+    pub split_tile_data: bool,
+
+    /// Use this flag if a header file that declares the generated constants and arrays should be generated for
+    /// every output file (banked assets always receive a header file)
+    #[arg(long = "headers")]
+    pub generate_header_files: bool,
+
+    /// Use this flag to print a table with the tile count, the sizes of the tile data and map data, the number of
+    /// metatiles, the occupied slots of the three VRAM tile blocks, the used palettes and the uncompressed and compressed size of every asset
+    #[arg(long = "report")]
+    pub print_report: bool,
+
+    /// Path of a JSON file, that the report of every asset is written to (see --report)
+    #[arg(long = "report_json", value_name = "file_path")]
+    pub report_json_path: Option<PathBuf>,
+
+    /// Use this flag to remove the tiles that aren't used by any of the supplied maps (or belong to an Aseprite
+    /// animation) from the generated tileset. The tile indices of all maps are remapped accordingly.
+    #[arg(long = "prune")]
+    pub prune_tileset: bool,
+
+    /// Use this flag to store the tile data in the 1bpp format (8 bytes per tile, see GBDK's set_bkg_1bpp_data),
+    /// e.g. for fonts. The tileset may only use two palette indices, the higher one becomes the foreground color.
+    #[arg(long = "1bpp")]
+    pub use_1bpp: bool,
+
+    /// Path of a text file with the characters of a font tileset, in the order of the glyphs in the font sheet
+    /// (row by row, without the reference tile). Line breaks are ignored, so every row of the sheet can be written
+    /// in its own line. Generates lookup tables from characters to tile indices and an RGBDS charmap include file
+    /// "<tileset>_charmap.inc". Non-ASCII characters (e.g. umlauts) are supported.
+    #[arg(long = "font_characters", value_name = "file_path", conflicts_with = "prune_tileset")]
+    pub font_characters_path: Option<PathBuf>,
+
+    /// Use this flag to generate a table with the pixel width of every glyph of the font for variable width font
+    /// rendering. The width of a glyph reaches up to its rightmost column with a pixel that doesn't have the
+    /// background palette index (0, or the background color of --1bpp), so glyphs should be left-aligned.
+    #[arg(long = "vwf", requires = "font_characters_path")]
+    pub variable_width_font: bool,
+
+    /// Pixel width of glyphs without any pixels (e.g. the space) for --vwf
+    #[arg(long = "vwf_empty_glyph_width", value_name = "width", default_value_t = 4)]
+    pub vwf_empty_glyph_width: u8,
+
+    /// Paths of text files (.txt with lines "name: text", .json with an object of texts or .csv with rows name,text),
+    /// whose texts are encoded into byte arrays of tile indices of the font (see --font_characters). Line breaks
+    /// and \n become TEXT_NEWLINE, \p becomes TEXT_PAUSE and every text ends with TEXT_END.
+    /// Characters without a glyph in the font are an error.
+    #[arg(long = "text", value_name = "path_list", num_args=1.., requires = "font_characters_path")]
+    pub text_file_paths: Vec<PathBuf>,
+
+    /// Full-screen images (160x144, e.g. title screens) with up to 360 distinct tiles, that receive their own
+    /// tile data instead of using the reference tileset. If a screen contains more than 256 distinct tiles,
+    /// its upper part uses the VRAM blocks $8000/$8800 and its lower part the blocks $9000/$8800, so LCDC
+    /// bit 4 has to be cleared mid-frame at the generated line. The tile data of every used block is written.
+    #[arg(long = "full_screen", value_name = "path_list", num_args=1..)]
+    pub full_screen_image_paths: Vec<PathBuf>,
+
+    /// Same as --full_screen, but for the GBC: tiles that don't fit into VRAM bank 0 are placed in VRAM
+    /// bank 1, which an additional background-attributes array selects, so no mid-frame switch is needed.
+    #[arg(long = "full_screen_mwa", visible_alias = "full_screen_with_attributes", value_name = "path_list", num_args=1..)]
+    pub full_screen_image_with_attributes_paths: Vec<PathBuf>,
+
+    /// Maximum number of distinct tiles of full-screen images (e.g. 256 to avoid the mid-frame switch). The most
+    /// similar tiles (the fewest different pixels, on the GBC including flipped versions) are merged until the
+    /// maximum is met. A preview image "<screen>_preview.png" highlights the substituted tiles in red.
+    #[arg(long = "full_screen_max_tiles", value_name = "N")]
+    pub full_screen_max_tile_count: Option<usize>,
+
+    /// Path of a 256x224 image, that is converted into a Super Game Boy border: 4bpp SNES tile data
+    /// (at most 256 tiles after removing duplicates and flipped duplicates), a 32x28 tilemap of SNES
+    /// attribute words and up to three palettes of 15 colors. The center window (48,40)-(207,183), in
+    /// which the Game Boy screen is shown, has to be transparent.
+    #[arg(long = "sgb_border", value_name = "file_path")]
+    pub sgb_border_path: Option<PathBuf>,
+
+    /// Color of the SGB border that is transparent, in addition to pixels with an alpha value below 128
+    #[arg(long = "sgb_transparent_color", value_name = "RRGGBB", value_parser = parse_rgb_color, requires = "sgb_border_path")]
+    pub sgb_border_transparent_color: Option<Rgb<u8>>,
+
+    /// Suffix of colored versions of maps (e.g. "title_sgb.png" for "title.png" and the suffix "_sgb"). Maps
+    /// supplied via -m/--map or --mwa, that have a colored version, have to be the size of the screen (160x144).
+    /// Up to four SGB palettes are derived from the colors of their palette indices and packets are generated,
+    /// that set the palettes (PAL01/PAL23) and assign them to the tiles of the screen (ATTR_BLK/ATTR_CHR).
+    #[arg(long = "sgb_colors", value_name = "suffix", num_args = 0..=1, default_missing_value = "_sgb")]
+    pub sgb_colored_screen_suffix: Option<String>,
+
+    /// DMG shades of the colors of the reference tileset's palette, in the form RRGGBB=shade with the shades
+    /// 0 (white) to 3 (black), e.g. --dmg_shades ffffff=0 000000=3. Generates the value of the BGP register
+    /// (which can be written to OBP0/OBP1 as well). Colors that aren't listed are displayed with the shade of
+    /// the same number as their palette index.
+    #[arg(long = "dmg_shades", value_name = "RRGGBB=shade list", num_args=1.., value_parser = parse_dmg_shade_color, conflicts_with = "dmg_shades_by_luminance")]
+    pub dmg_shade_colors: Vec<DmgShadeColor>,
+
+    /// Use this flag to display every color of the reference tileset's palette with the DMG shade that is closest
+    /// to its luminance and generate the value of the BGP register (which can be written to OBP0/OBP1 as well)
+    #[arg(long = "dmg_shades_by_luminance")]
+    pub dmg_shades_by_luminance: bool,
+
     /// Use this flag if the generated output file(s) should use hex notation instead of the
     /// default binary notation. 
     // Binary was choosen as default since the pixels are represented
@@ -0,0 +1,101 @@
+// This module reads collision (behavior) maps from companion images of maps. Every color of a collision
+// image stands for a configurable value (e.g. solid, ladder, water) and every tile of the map receives the
+// value of the color that covers most of its pixels.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use image::Rgb;
+
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+use crate::read_aseprite::*;
+use crate::read_input::*;
+
+/// A color of collision images and the collision value it stands for
+#[derive(Clone)]
+pub struct CollisionColor {
+    pub color: Rgb<u8>,
+    pub value: u8,
+}
+
+/// Parses a collision color of the form RRGGBB=value (e.g. ff0000=1)
+pub fn parse_collision_color(str: &str) -> Result<CollisionColor, String> {
+    let Some((color_str, value_str)) = str.split_once('=') else {
+        return Err(String::from("expected the form RRGGBB=value"));
+    };
+    let color = parse_rgb_color(color_str)?;
+    let value = value_str.parse::<u8>().map_err(|_| format!("\"{}\" is not a value between 0 and 255", value_str))?;
+    return Ok(CollisionColor {
+        color: color,
+        value: value,
+    });
+}
+
+pub struct CollisionSettings {
+    pub collision_colors: Vec<CollisionColor>,
+    // The collision image of the map "level.png" is "level<suffix>.png"
+    pub collision_image_suffix: String,
+}
+
+/// Returns the path of the collision image of a map, if it exists
+pub fn collision_image_path_for_map_path(map_path: &Path, collision_settings: &CollisionSettings) -> Option<PathBuf> {
+    companion_path_for_map_path(map_path, collision_settings.collision_image_suffix.as_str())
+}
+
+/// Reads the collision image at collision_image_path, which needs to have the same dimensions as the visual map.
+/// Colors that aren't listed in the collision settings stand for the value 0.
+pub fn collision_array_from_image_path(collision_image_path: &Path, map_path: &Path, map_image_dimensions: (u32, u32), aseprite_layer_selection: &AsepriteLayerSelection, collision_settings: &CollisionSettings) -> TilemapByteArray {
+    let collision_image = rgbimage_from_path(collision_image_path, aseprite_layer_selection);
+
+    if collision_image.dimensions() != map_image_dimensions {
+        panic!(
+            "The collision image \"{}\" ({}x{}) doesn't have the same dimensions as the map \"{}\" ({}x{})",
+            collision_image_path.imm_to_str(), collision_image.width(), collision_image.height(), map_path.imm_to_str(), map_image_dimensions.0, map_image_dimensions.1
+        );
+    }
+
+    let mut res: TilemapByteArray = unsafe { std::mem::zeroed() };
+
+    for tile_x in 0..(collision_image.width() / 8) {
+        for tile_y in 0..(collision_image.height() / 8) {
+            let mut value_pixel_counts: HashMap<u8, u32> = HashMap::new();
+            for x in 0..8 {
+                for y in 0..8 {
+                    let pixel = collision_image.get_pixel(tile_x * 8 + x, tile_y * 8 + y);
+                    let value = collision_settings.collision_colors.iter().find(|x| x.color == *pixel).map(|x| x.value).unwrap_or(0);
+                    *value_pixel_counts.entry(value).or_insert(0) += 1;
+                }
+            }
+            res.assign(tile_x, tile_y, most_common_value(&value_pixel_counts));
+        }
+    }
+
+    return res;
+}
+
+/// Reduces a collision array to one value per metatile (the value of most of the metatile's tiles),
+/// row by row, so that it is aligned with the metatile map
+pub fn metatile_collision_array(collision_array: &TilemapByteArray, metatile_size: u32) -> Vec<u8> {
+    let width_in_metatiles = 32 / metatile_size;
+    let mut res = Vec::new();
+
+    for block_y in 0..width_in_metatiles {
+        for block_x in 0..width_in_metatiles {
+            let mut value_tile_counts: HashMap<u8, u32> = HashMap::new();
+            for y in (block_y * metatile_size)..((block_y + 1) * metatile_size) {
+                for x in (block_x * metatile_size)..((block_x + 1) * metatile_size) {
+                    *value_tile_counts.entry(collision_array.get(x, y)).or_insert(0) += 1;
+                }
+            }
+            res.push(most_common_value(&value_tile_counts));
+        }
+    }
+
+    return res;
+}
+
+// On a tie, the higher value wins, so that e.g. a half solid tile is solid
+fn most_common_value(value_counts: &HashMap<u8, u32>) -> u8 {
+    value_counts.iter().max_by_key(|(value, count)| (**count, **value)).map(|(value, _)| *value).unwrap_or(0)
+}
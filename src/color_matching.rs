@@ -0,0 +1,103 @@
+// This module tolerates colors that are slightly off or differ completely from the palette colors, as they
+// result from exporting images with different editors. Such colors are replaced with the palette color they
+// stand for before the palette indices are read, either because every channel is within a tolerance or
+// because the color is an alias of a palette index.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use image::Rgb;
+
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+use crate::read_input::*;
+
+/// A color that stands for the palette index
+#[derive(Clone)]
+pub struct ColorAlias {
+    pub color: Rgb<u8>,
+    pub palette_index: u8,
+}
+
+/// Parses a color alias of the form RRGGBB=palette_index (e.g. 0f380f=3)
+pub fn parse_color_alias(str: &str) -> Result<ColorAlias, String> {
+    let Some((color_str, palette_index_str)) = str.split_once('=') else {
+        return Err(String::from("expected the form RRGGBB=palette_index"));
+    };
+    let color = parse_rgb_color(color_str)?;
+    let palette_index = match palette_index_str.parse::<u8>() {
+        Ok(palette_index) if palette_index <= 3 => palette_index,
+        _ => return Err(format!("\"{}\" is not a palette index between 0 and 3", palette_index_str)),
+    };
+    return Ok(ColorAlias {
+        color: color,
+        palette_index: palette_index,
+    });
+}
+
+pub struct ColorMatchingSettings {
+    // Maximum difference of every channel of a color to a palette color
+    pub tolerance: u8,
+    pub color_aliases: Vec<ColorAlias>,
+    // Paths of the images that use every alias, for the summary of used aliases
+    pub color_alias_usages: RefCell<Vec<BTreeSet<String>>>,
+}
+
+impl ColorMatchingSettings {
+    pub fn new(tolerance: u8, color_aliases: Vec<ColorAlias>) -> Self {
+        let color_alias_usages = RefCell::new(vec![BTreeSet::new(); color_aliases.len()]);
+        return ColorMatchingSettings {
+            tolerance: tolerance,
+            color_aliases: color_aliases,
+            color_alias_usages: color_alias_usages,
+        };
+    }
+}
+
+/// Replaces the aliases and the colors within the tolerance of palette colors with the palette colors.
+/// Images with palette indices are returned unchanged.
+pub fn palette_matched_tile_source_image(tile_source_image: TileSourceImage, color_palette: &ColorPalette, color_matching_settings: Option<&ColorMatchingSettings>, path: &Path) -> TileSourceImage {
+    match (tile_source_image, color_matching_settings) {
+        (TileSourceImage::Rgb(image), Some(color_matching_settings)) => TileSourceImage::Rgb(palette_matched_image(image, color_palette, color_matching_settings, path)),
+        (tile_source_image, _) => tile_source_image,
+    }
+}
+
+fn palette_matched_image(mut image: image::RgbImage, color_palette: &ColorPalette, color_matching_settings: &ColorMatchingSettings, path: &Path) -> image::RgbImage {
+    let channel_distance = |a: &Rgb<u8>, b: &Rgb<u8>| (0..3).map(|i| a[i].abs_diff(b[i])).max().unwrap();
+
+    for pixel in image.pixels_mut() {
+        if color_palette.contains(pixel) {
+            continue;
+        }
+        if let Some(alias_index) = color_matching_settings.color_aliases.iter().position(|x| x.color == *pixel) {
+            color_matching_settings.color_alias_usages.borrow_mut()[alias_index].insert(path.imm_to_str().to_string());
+            *pixel = color_palette[color_matching_settings.color_aliases[alias_index].palette_index as usize];
+            continue;
+        }
+        // The closest palette color within the tolerance
+        if let Some(palette_color) = color_palette.iter()
+            .filter(|x| channel_distance(x, pixel) <= color_matching_settings.tolerance)
+            .min_by_key(|x| (0..3).map(|i| x[i].abs_diff(pixel[i]) as u32).sum::<u32>())
+        {
+            *pixel = *palette_color;
+        }
+    }
+
+    return image;
+}
+
+/// Prints the images that use every color alias and warns about unused aliases
+pub fn print_color_alias_usages(color_matching_settings: &ColorMatchingSettings) {
+    for (color_alias, usages) in color_matching_settings.color_aliases.iter().zip(color_matching_settings.color_alias_usages.borrow().iter()) {
+        let color_alias_string = format!(
+            "{:02X}{:02X}{:02X}={}",
+            color_alias.color[0], color_alias.color[1], color_alias.color[2], color_alias.palette_index
+        );
+        match usages.is_empty() {
+            true => print_warning(format!("The color alias {} isn't used by any image", color_alias_string).as_str()),
+            false => println!("The color alias {} is used by: {}", color_alias_string, usages.iter().cloned().collect::<Vec<String>>().join(", ")),
+        }
+    }
+}
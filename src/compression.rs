@@ -0,0 +1,485 @@
+// This module contains the compressors that can be applied to the generated data and a decompressor for
+// every format. Every compressed byte string is decompressed again right away and compared against the
+// original data, so that a broken compressor can never silently produce corrupt assets.
+
+use clap::ValueEnum;
+
+use crate::input_data_representation_types::*;
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CompressionFormat {
+    /// No compression
+    None,
+    /// The format of GBDK's gbcompress tool, decompressed by gb_decompress()
+    Gbcompress,
+    /// PB16, a packbits like format for 2bpp data, that decompresses in units of 8 bytes
+    Pb16,
+    /// A simple run length encoding (see the README for the format)
+    Rle,
+}
+
+/// Decides whether maps are compressed as a whole or in chunks of single rows or columns, which allows
+/// scrolling engines to decompress a single row or column of a map
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MapCompressionChunking {
+    Whole,
+    Rows,
+    Columns,
+}
+
+/// How the byte arrays of maps (tile indices, attributes, ...) are compressed
+#[derive(Clone, Copy)]
+pub struct MapCompression {
+    pub compression_format: CompressionFormat,
+    pub chunking: MapCompressionChunking,
+}
+
+/// Compresses the map as a whole or every row or column separately. Returns the compressed chunks, which are
+/// stored one after another, and the offset of every chunk.
+pub fn compressed_map_chunks(byte_array: &TilemapByteArray, map_compression: MapCompression) -> (Vec<u8>, Vec<usize>) {
+    let chunks: Vec<Vec<u8>> = match map_compression.chunking {
+        MapCompressionChunking::Whole => vec![(0..32).flat_map(|y| (0..32).map(move |x| (x, y))).map(|(x, y)| byte_array.get(x, y)).collect()],
+        MapCompressionChunking::Rows => (0..32).map(|y| (0..32).map(|x| byte_array.get(x, y)).collect()).collect(),
+        MapCompressionChunking::Columns => (0..32).map(|x| (0..32).map(|y| byte_array.get(x, y)).collect()).collect(),
+    };
+
+    let mut compressed_bytes = Vec::new();
+    let mut chunk_offsets = Vec::new();
+    for chunk in chunks.iter() {
+        chunk_offsets.push(compressed_bytes.len());
+        compressed_bytes.extend(compress(chunk, map_compression.compression_format));
+    }
+
+    return (compressed_bytes, chunk_offsets);
+}
+
+/// Compresses data and checks that it decompresses to the original data again
+pub fn compress(data: &[u8], compression_format: CompressionFormat) -> Vec<u8> {
+    let res = match compression_format {
+        CompressionFormat::None => data.to_vec(),
+        CompressionFormat::Gbcompress => gbcompress_compress(data),
+        CompressionFormat::Pb16 => pb16_compress(data),
+        CompressionFormat::Rle => rle_compress(data),
+    };
+
+    if decompress(&res, compression_format, data.len()) != data {
+        panic!("Internal error: {:?} compressed data doesn't decompress to the original data", compression_format);
+    }
+
+    return res;
+}
+
+/// Decompresses data. The uncompressed size is only needed by formats that don't contain an end marker (PB16).
+pub fn decompress(data: &[u8], compression_format: CompressionFormat, uncompressed_size: usize) -> Vec<u8> {
+    match compression_format {
+        CompressionFormat::None => data.to_vec(),
+        CompressionFormat::Gbcompress => gbcompress_decompress(data),
+        CompressionFormat::Pb16 => pb16_decompress(data, uncompressed_size),
+        CompressionFormat::Rle => rle_decompress(data),
+    }
+}
+
+// gbcompress
+//
+// Every command starts with a byte whose upper two bits select the command and whose lower six bits
+// store the length - 1 (1-64):
+//  00: repeat the following byte
+//  01: repeat the following two bytes (the length counts the repetitions of the pair)
+//  10: copy bytes that were already decompressed, the following two bytes (little endian) contain
+//      the negative offset from the current position
+//  11: copy the following bytes
+// A command byte of 0x00 marks the end of the data.
+
+const GBCOMPRESS_MAX_LENGTH: usize = 64;
+
+pub fn gbcompress_compress(data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::new();
+    let mut literal_start = 0;
+    let mut position = 0;
+
+    let flush_literals = |res: &mut Vec<u8>, literal_start: usize, position: usize| {
+        for chunk in data[literal_start..position].chunks(GBCOMPRESS_MAX_LENGTH) {
+            res.push(0xC0 | (chunk.len() - 1) as u8);
+            res.extend_from_slice(chunk);
+        }
+    };
+
+    while position < data.len() {
+        // Byte run
+        let mut byte_run_length = 1;
+        while position + byte_run_length < data.len() && byte_run_length < GBCOMPRESS_MAX_LENGTH && data[position + byte_run_length] == data[position] {
+            byte_run_length += 1;
+        }
+
+        // Word run, counted in words
+        let mut word_run_length = 0;
+        if position + 1 < data.len() {
+            word_run_length = 1;
+            while position + 2 * word_run_length + 1 < data.len() && word_run_length < GBCOMPRESS_MAX_LENGTH
+                && data[position + 2 * word_run_length] == data[position]
+                && data[position + 2 * word_run_length + 1] == data[position + 1] {
+                word_run_length += 1;
+            }
+        }
+
+        // Longest match in the already processed data
+        let mut string_length = 0;
+        let mut string_offset = 0;
+        let window_start = position.saturating_sub(u16::MAX as usize);
+        for match_start in window_start..position {
+            let mut length = 0;
+            while position + length < data.len() && length < GBCOMPRESS_MAX_LENGTH && data[match_start + length] == data[position + length] {
+                length += 1;
+            }
+            if length > string_length {
+                string_length = length;
+                string_offset = position - match_start;
+            }
+        }
+
+        // Pick the command that saves the most bytes (a byte run of length 1 would be the end marker)
+        let byte_run_savings = byte_run_length as i32 - 2;
+        let word_run_savings = 2 * word_run_length as i32 - 3;
+        let string_savings = string_length as i32 - 3;
+        let best_savings = byte_run_savings.max(word_run_savings).max(string_savings);
+
+        if best_savings <= 0 {
+            position += 1;
+            continue;
+        }
+
+        flush_literals(&mut res, literal_start, position);
+
+        if best_savings == byte_run_savings {
+            res.push((byte_run_length - 1) as u8);
+            res.push(data[position]);
+            position += byte_run_length;
+        } else if best_savings == word_run_savings {
+            res.push(0x40 | (word_run_length - 1) as u8);
+            res.push(data[position]);
+            res.push(data[position + 1]);
+            position += 2 * word_run_length;
+        } else {
+            let negative_offset = (string_offset as u16).wrapping_neg();
+            res.push(0x80 | (string_length - 1) as u8);
+            res.extend_from_slice(&negative_offset.to_le_bytes());
+            position += string_length;
+        }
+        literal_start = position;
+    }
+
+    flush_literals(&mut res, literal_start, position);
+    res.push(0x00);
+
+    return res;
+}
+
+pub fn gbcompress_decompress(data: &[u8]) -> Vec<u8> {
+    let mut res: Vec<u8> = Vec::new();
+    let mut position = 0;
+
+    loop {
+        let command = data[position];
+        position += 1;
+        if command == 0x00 {
+            break;
+        }
+        let length = (command & 0x3F) as usize + 1;
+
+        match command >> 6 {
+            0 => {
+                res.extend(std::iter::repeat(data[position]).take(length));
+                position += 1;
+            },
+            1 => {
+                for _ in 0..length {
+                    res.push(data[position]);
+                    res.push(data[position + 1]);
+                }
+                position += 2;
+            },
+            2 => {
+                let negative_offset = u16::from_le_bytes([data[position], data[position + 1]]);
+                position += 2;
+                let start = res.len() - negative_offset.wrapping_neg() as usize;
+                for i in 0..length {
+                    res.push(res[start + i]);
+                }
+            },
+            _ => {
+                res.extend_from_slice(&data[position..(position + length)]);
+                position += length;
+            },
+        }
+    }
+
+    return res;
+}
+
+// PB16
+//
+// The data is split into packets of 8 bytes. Every packet starts with a control byte, whose bits (starting
+// with the most significant bit) tell for every byte of the packet, whether it is a copy of the byte two
+// positions before it (1) or whether it follows as a literal (0). For 2bpp tile data, this repeats the same
+// bitplane of the previous line. The two bytes before the start of the data are considered to be 0.
+
+pub fn pb16_compress(data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::new();
+    let mut previous_bytes: [u8; 2] = [0, 0];
+
+    for packet in data.chunks(8) {
+        let mut control_byte: u8 = 0;
+        let mut literals = Vec::new();
+        for (i, byte) in packet.iter().enumerate() {
+            if *byte == previous_bytes[1] {
+                control_byte |= 0x80 >> i;
+            } else {
+                literals.push(*byte);
+            }
+            previous_bytes = [*byte, previous_bytes[0]];
+        }
+        res.push(control_byte);
+        res.extend(literals);
+    }
+
+    return res;
+}
+
+pub fn pb16_decompress(data: &[u8], uncompressed_size: usize) -> Vec<u8> {
+    let mut res = Vec::new();
+    let mut previous_bytes: [u8; 2] = [0, 0];
+    let mut position = 0;
+
+    while res.len() < uncompressed_size {
+        let control_byte = data[position];
+        position += 1;
+        for i in 0..8.min(uncompressed_size - res.len()) {
+            let byte = if (control_byte & (0x80 >> i)) != 0 {
+                previous_bytes[1]
+            } else {
+                position += 1;
+                data[position - 1]
+            };
+            res.push(byte);
+            previous_bytes = [byte, previous_bytes[0]];
+        }
+    }
+
+    return res;
+}
+
+// RLE
+//
+// A control byte of 0x01-0x7F is followed by that many literal bytes, a control byte of 0x81-0xFF is
+// followed by a single byte that is repeated (control byte & 0x7F) times. A control byte of 0x00 marks
+// the end of the data.
+
+const RLE_MAX_LENGTH: usize = 0x7F;
+
+pub fn rle_compress(data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::new();
+    let mut literals: Vec<u8> = Vec::new();
+    let mut position = 0;
+
+    let flush_literals = |res: &mut Vec<u8>, literals: &mut Vec<u8>| {
+        for chunk in literals.chunks(RLE_MAX_LENGTH) {
+            res.push(chunk.len() as u8);
+            res.extend_from_slice(chunk);
+        }
+        literals.clear();
+    };
+
+    while position < data.len() {
+        let mut run_length = 1;
+        while position + run_length < data.len() && run_length < RLE_MAX_LENGTH && data[position + run_length] == data[position] {
+            run_length += 1;
+        }
+
+        // Runs of 2 bytes only pay off if they don't interrupt a literal sequence
+        if run_length >= 3 || (run_length == 2 && literals.is_empty()) {
+            flush_literals(&mut res, &mut literals);
+            res.push(0x80 | run_length as u8);
+            res.push(data[position]);
+            position += run_length;
+        } else {
+            literals.push(data[position]);
+            position += 1;
+        }
+    }
+
+    flush_literals(&mut res, &mut literals);
+    res.push(0x00);
+
+    return res;
+}
+
+pub fn rle_decompress(data: &[u8]) -> Vec<u8> {
+    let mut res = Vec::new();
+    let mut position = 0;
+
+    loop {
+        let control_byte = data[position];
+        position += 1;
+        if control_byte == 0x00 {
+            break;
+        }
+        let length = (control_byte & 0x7F) as usize;
+        if (control_byte & 0x80) != 0 {
+            res.extend(std::iter::repeat(data[position]).take(length));
+            position += 1;
+        } else {
+            res.extend_from_slice(&data[position..(position + length)]);
+            position += length;
+        }
+    }
+
+    return res;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPRESSION_FORMATS: [CompressionFormat; 4] = [CompressionFormat::None, CompressionFormat::Gbcompress, CompressionFormat::Pb16, CompressionFormat::Rle];
+
+    // Tile data like bytes: runs, repeated words, repeated lines and noise
+    fn mixed_data(length: usize) -> Vec<u8> {
+        let mut state: u32 = 12345;
+        return (0..length).map(|i| match (i / 40) % 4 {
+            0 => 0xAA,
+            1 => if i % 2 == 0 { 0x3C } else { 0x7E },
+            2 => (i % 8) as u8,
+            _ => {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            },
+        }).collect();
+    }
+
+    fn assert_round_trip(data: &[u8]) {
+        for compression_format in COMPRESSION_FORMATS {
+            let compressed = compress(data, compression_format);
+            assert_eq!(decompress(&compressed, compression_format, data.len()), data, "{:?} round trip of {} bytes", compression_format, data.len());
+        }
+    }
+
+    #[test]
+    fn round_trip_empty_input() {
+        assert_round_trip(&[]);
+        assert_eq!(gbcompress_compress(&[]), vec![0x00]);
+        assert_eq!(pb16_compress(&[]), Vec::<u8>::new());
+        assert_eq!(rle_compress(&[]), vec![0x00]);
+    }
+
+    #[test]
+    fn round_trip_single_byte() {
+        assert_round_trip(&[0x42]);
+        assert_eq!(gbcompress_compress(&[0x42]), vec![0xC0, 0x42, 0x00]);
+        assert_eq!(pb16_compress(&[0x42]), vec![0x00, 0x42]);
+        assert_eq!(pb16_compress(&[0x00]), vec![0x80]);
+        assert_eq!(rle_compress(&[0x42]), vec![0x01, 0x42, 0x00]);
+    }
+
+    #[test]
+    fn round_trip_runs_longer_than_the_length_limits() {
+        for length in [GBCOMPRESS_MAX_LENGTH + 1, RLE_MAX_LENGTH + 1, 300] {
+            assert_round_trip(&vec![0x55; length]);
+        }
+        // A run of 100 bytes is stored as 50 repetitions of a word, since byte runs are limited to 64 bytes
+        assert_eq!(gbcompress_compress(&[0x07; 100]), vec![0x71, 0x07, 0x07, 0x00]);
+        assert_eq!(rle_compress(&[0x55; 300]), vec![0xFF, 0x55, 0xFF, 0x55, 0xAE, 0x55, 0x00]);
+        assert_eq!(pb16_compress(&[0xAB; 20]), vec![0x3F, 0xAB, 0xAB, 0xFF, 0xF0]);
+    }
+
+    #[test]
+    fn round_trip_literals_longer_than_the_length_limits() {
+        let data: Vec<u8> = (0..200).map(|x| x as u8).collect();
+        assert_round_trip(&data);
+        let mut expected = vec![0x7F];
+        expected.extend(0..127u8);
+        expected.push(0x49);
+        expected.extend(127..200u8);
+        expected.push(0x00);
+        assert_eq!(rle_compress(&data), expected);
+    }
+
+    #[test]
+    fn round_trip_inputs_over_256_bytes() {
+        for length in [257, 1000, 4096] {
+            assert_round_trip(&mixed_data(length));
+        }
+    }
+
+    #[test]
+    fn gbcompress_decompresses_known_data() {
+        assert_eq!(gbcompress_decompress(&[0x03, 0xAA, 0x00]), vec![0xAA; 4]);
+        assert_eq!(gbcompress_decompress(&[0x41, 0x12, 0x34, 0x00]), vec![0x12, 0x34, 0x12, 0x34]);
+        // 3 literals, followed by a copy of 3 bytes from 3 bytes before
+        assert_eq!(gbcompress_decompress(&[0xC2, 0x01, 0x02, 0x03, 0x82, 0xFD, 0xFF, 0x00]), vec![0x01, 0x02, 0x03, 0x01, 0x02, 0x03]);
+        // Overlapping copies repeat the copied bytes
+        assert_eq!(gbcompress_decompress(&[0xC1, 0x01, 0x02, 0x83, 0xFE, 0xFF, 0x00]), vec![0x01, 0x02, 0x01, 0x02, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn pb16_decompresses_known_data() {
+        assert_eq!(pb16_decompress(&[0x00, 1, 2, 3, 4, 5, 6, 7, 8], 8), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(pb16_decompress(&[0x3F, 0x11, 0x22], 8), vec![0x11, 0x22, 0x11, 0x22, 0x11, 0x22, 0x11, 0x22]);
+        // The bytes before the data are 0 and packets continue the previous packet
+        assert_eq!(pb16_decompress(&[0xFF, 0x7F, 0x09], 10), vec![0, 0, 0, 0, 0, 0, 0, 0, 0x09, 0]);
+    }
+
+    // A map with runs (empty rows), repeated words and unique tiles
+    fn test_map() -> TilemapByteArray {
+        let mut res: TilemapByteArray = unsafe { std::mem::zeroed() };
+        for y in 0..32u32 {
+            for x in 0..32u32 {
+                let byte = match y % 4 {
+                    0 => 0,
+                    1 => (x % 2) as u8 + 1,
+                    2 => (x * 7 + y * 3) as u8,
+                    _ => if x < 20 { 5 } else { x as u8 },
+                };
+                res.assign(x, y, byte);
+            }
+        }
+        return res;
+    }
+
+    #[test]
+    fn map_chunks_decompress_to_their_rows_and_columns() {
+        let map = test_map();
+        // Uncompressed maps aren't chunked
+        for compression_format in COMPRESSION_FORMATS.into_iter().filter(|x| *x != CompressionFormat::None) {
+            for chunking in [MapCompressionChunking::Rows, MapCompressionChunking::Columns] {
+                let (compressed_bytes, chunk_offsets) = compressed_map_chunks(&map, MapCompression { compression_format: compression_format, chunking: chunking });
+                assert_eq!(chunk_offsets.len(), 32);
+                assert_eq!(chunk_offsets[0], 0);
+                for (chunk_index, chunk_offset) in chunk_offsets.iter().enumerate() {
+                    let expected: Vec<u8> = match chunking {
+                        MapCompressionChunking::Rows => (0..32).map(|x| map.get(x, chunk_index)).collect(),
+                        _ => (0..32).map(|y| map.get(chunk_index, y)).collect(),
+                    };
+                    // Like a scrolling engine, the chunk is decompressed from its offset without knowing where it ends
+                    assert_eq!(decompress(&compressed_bytes[*chunk_offset..], compression_format, 32), expected, "{:?} {:?} chunk {}", compression_format, chunking, chunk_index);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn whole_map_decompresses_to_the_map() {
+        let map = test_map();
+        let expected: Vec<u8> = (0..32).flat_map(|y| (0..32).map(move |x| (x, y))).map(|(x, y)| map.get(x, y)).collect();
+        for compression_format in COMPRESSION_FORMATS {
+            let (compressed_bytes, chunk_offsets) = compressed_map_chunks(&map, MapCompression { compression_format: compression_format, chunking: MapCompressionChunking::Whole });
+            assert_eq!(chunk_offsets, vec![0]);
+            assert_eq!(decompress(&compressed_bytes, compression_format, 32 * 32), expected);
+        }
+    }
+
+    #[test]
+    fn rle_decompresses_known_data() {
+        assert_eq!(rle_decompress(&[0x83, 0x11, 0x02, 0xAA, 0xBB, 0x00]), vec![0x11, 0x11, 0x11, 0xAA, 0xBB]);
+        assert_eq!(rle_decompress(&[0x00]), Vec::<u8>::new());
+    }
+}
@@ -0,0 +1,63 @@
+// This module decides which of the four DMG shades (0 = white ... 3 = black) every palette index of the reference
+// tileset is displayed as, and encodes this mapping into the value of the BGP palette register (which can be written
+// to OBP0/OBP1 as well).
+
+use image::Rgb;
+
+use crate::input_data_representation_types::*;
+use crate::read_input::*;
+
+/// A color of the reference tileset and the DMG shade it should be displayed as
+#[derive(Clone)]
+pub struct DmgShadeColor {
+    pub color: Rgb<u8>,
+    pub shade: u8,
+}
+
+/// Parses a DMG shade color of the form RRGGBB=shade (e.g. 555555=2)
+pub fn parse_dmg_shade_color(str: &str) -> Result<DmgShadeColor, String> {
+    let Some((color_str, shade_str)) = str.split_once('=') else {
+        return Err(String::from("expected the form RRGGBB=shade"));
+    };
+    let color = parse_rgb_color(color_str)?;
+    let shade = match shade_str.parse::<u8>() {
+        Ok(shade) if shade <= 3 => shade,
+        _ => return Err(format!("\"{}\" is not a shade between 0 (white) and 3 (black)", shade_str)),
+    };
+    return Ok(DmgShadeColor {
+        color: color,
+        shade: shade,
+    });
+}
+
+/// Returns the shade of every palette index. Palette indices whose color isn't listed keep the shade of
+/// the same number (as with the default palette register value 0xE4).
+pub fn dmg_shades_from_shade_colors(color_palette: &ColorPalette, dmg_shade_colors: &Vec<DmgShadeColor>) -> [u8; 4] {
+    let mut res = [0, 1, 2, 3];
+    for dmg_shade_color in dmg_shade_colors.iter() {
+        let Some(palette_index) = color_palette.iter().position(|x| *x == dmg_shade_color.color) else {
+            panic!(
+                "The DMG shade color {:02X}{:02X}{:02X} isn't a color of the reference tileset's palette",
+                dmg_shade_color.color[0], dmg_shade_color.color[1], dmg_shade_color.color[2]
+            );
+        };
+        res[palette_index] = dmg_shade_color.shade;
+    }
+    return res;
+}
+
+/// Returns the shade of every palette index, that is closest to the luminance of its color
+pub fn dmg_shades_by_luminance(color_palette: &ColorPalette) -> [u8; 4] {
+    let mut res = [0; 4];
+    for (palette_index, color) in color_palette.iter().enumerate() {
+        let luminance = 0.299 * color[0] as f64 + 0.587 * color[1] as f64 + 0.114 * color[2] as f64;
+        res[palette_index] = 3 - (luminance * 3.0 / 255.0).round() as u8;
+    }
+    return res;
+}
+
+/// Encodes the shades of the palette indices into a palette register value, bits 2i and 2i+1 contain
+/// the shade of the palette index i
+pub fn dmg_palette_register_value(dmg_shades: &[u8; 4]) -> u8 {
+    dmg_shades.iter().enumerate().fold(0, |value, (palette_index, shade)| value | (shade << (2 * palette_index)))
+}
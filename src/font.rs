@@ -0,0 +1,102 @@
+// This module pairs the tiles of a font tileset with the characters they show. The characters are read from a
+// character list, that contains the glyphs of the font sheet in reading order (row by row). The resulting
+// character to tile index mapping is written as C lookup tables and as an RGBDS charmap include file.
+
+use std::path::Path;
+
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+use crate::read_aseprite::*;
+use crate::read_input::*;
+
+/// Reads the characters of a character list file. Line breaks only separate the rows of the font sheet and
+/// aren't characters themselves, every other character (including spaces) is.
+pub fn font_characters_from_path(path: &Path) -> Vec<char> {
+    let file_content = std::fs::read_to_string(path)
+        .expect(format!("Couldn't read the font character list: {}", path.imm_to_str()).as_str());
+    file_content.chars().filter(|x| *x != '\n' && *x != '\r').collect()
+}
+
+/// Assigns the characters to the tile indices of the tiles of the font sheet in reading order. The reference
+/// tile doesn't show a glyph and is skipped. For reference tileset directories, the reading order is the
+/// order of the tile indices.
+pub fn font_character_tile_indices(characters: &Vec<char>, reference_tileset_path: &Path, aseprite_layer_selection: &AsepriteLayerSelection, tile_count: usize, has_reference_tile: bool) -> Vec<(char, u8)> {
+    let tile_indices_in_reading_order: Vec<u8> = match reference_tileset_path.is_dir() {
+        true => (0..tile_count).map(|x| x as u8).collect(),
+        false => {
+            let (width, height) = rgbimage_from_path(reference_tileset_path, aseprite_layer_selection).dimensions();
+            (0..(height / 8))
+                .flat_map(|tile_y| (0..(width / 8)).map(move |tile_x| (tile_x, tile_y)))
+                .filter_map(|(tile_x, tile_y)| tile_index_from_tileset_image_position(tile_x, tile_y, height / 8, has_reference_tile))
+                .collect()
+        },
+    };
+
+    if characters.len() > tile_indices_in_reading_order.len() {
+        panic!("The font character list contains {} characters, but the font tileset only contains {} tiles", characters.len(), tile_indices_in_reading_order.len());
+    }
+    if characters.len() < tile_indices_in_reading_order.len() {
+        print_warning(format!(
+            "The font character list contains only {} characters, so the last {} tiles of the font tileset don't receive a character",
+            characters.len(), tile_indices_in_reading_order.len() - characters.len()
+        ).as_str());
+    }
+
+    let mut res: Vec<(char, u8)> = Vec::new();
+    for (character, tile_index) in characters.iter().zip(tile_indices_in_reading_order.iter()) {
+        if res.iter().any(|(x, _)| x == character) {
+            panic!("The font character list contains the character '{}' more than once", character);
+        }
+        res.push((*character, *tile_index));
+    }
+    res.sort_by_key(|(character, _)| *character);
+
+    return res;
+}
+
+/// Measures the pixel width of the glyph of every tile for variable width font renderers: the rightmost column
+/// that contains a pixel without the background palette index, plus 1. Glyphs need to be left-aligned within their
+/// tile, since the width is measured from the left edge. Empty glyphs (e.g. the space) receive empty_glyph_width.
+pub fn glyph_widths(tile_info_vec: &Vec<TileInfo>, character_tile_indices: &Vec<(char, u8)>, background_palette_index: u8, empty_glyph_width: u8) -> Vec<u8> {
+    let column_is_empty = |tile_info: &TileInfo, x: u32| (0..8).all(|y| tile_info.color_array.get(x, y) == background_palette_index);
+
+    let mut not_left_aligned_characters = Vec::new();
+    let mut res = Vec::new();
+    for (tile_index, tile_info) in tile_info_vec.iter().enumerate() {
+        match (0..8).rev().find(|x| !column_is_empty(tile_info, *x)) {
+            Some(rightmost_column) => {
+                if column_is_empty(tile_info, 0) {
+                    not_left_aligned_characters.extend(character_tile_indices.iter().filter(|(_, x)| *x as usize == tile_index).map(|(x, _)| *x));
+                }
+                res.push(rightmost_column as u8 + 1);
+            },
+            None => res.push(empty_glyph_width),
+        }
+    }
+
+    if !not_left_aligned_characters.is_empty() {
+        print_warning(format!(
+            "The glyphs of the characters {} aren't left-aligned within their tiles, so their widths include the empty columns on their left",
+            not_left_aligned_characters.iter().map(|x| format!("'{}'", x)).collect::<Vec<String>>().join(", ")
+        ).as_str());
+    }
+
+    return res;
+}
+
+/// Returns the content of an RGBDS include file, that maps every character to its tile index via charmap
+pub fn rgbds_charmap_string(character_tile_indices: &Vec<(char, u8)>, file_name: &str) -> String {
+    let mut res = format!("; {} - Generated file by ITGBA\n", file_name);
+    for (character, tile_index) in character_tile_indices.iter() {
+        let escaped_character = match character {
+            '"' => String::from("\\\""),
+            '\\' => String::from("\\\\"),
+            // Braces start symbol interpolations in RGBDS strings
+            '{' => String::from("\\{"),
+            '}' => String::from("\\}"),
+            _ => character.to_string(),
+        };
+        res.push_str(format!("charmap \"{}\", ${:02X}\n", escaped_character, tile_index).as_str());
+    }
+    return res;
+}
@@ -0,0 +1,413 @@
+// This module converts full-screen images (e.g. title screens), whose tiles are mostly unique, into tile data and
+// a map. A screen can contain up to 360 distinct tiles, but the background can only address 256 tiles at once:
+// the blocks $8000 and $8800 (LCDC bit 4 set) or the blocks $9000 and $8800 (LCDC bit 4 cleared). On the DMG,
+// the upper part of the screen therefore uses the former and the lower part the latter blocks, with LCDC bit 4
+// being cleared mid-frame. On the GBC, the tiles are distributed across both VRAM banks instead.
+// Screens with too many distinct tiles can be reduced lossily, by substituting tiles with similar ones.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use image::Rgb;
+
+use crate::color_matching::*;
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+use crate::quantization::*;
+use crate::read_aseprite::*;
+use crate::read_input::*;
+
+const SCREEN_WIDTH_IN_TILES: u32 = 20;
+const SCREEN_HEIGHT_IN_TILES: u32 = 18;
+const VRAM_BLOCK_TILE_COUNT: usize = 128;
+
+// GBC background attribute bit that selects VRAM bank 1
+const ATTRIBUTE_VRAM_BANK_1: u8 = 0x08;
+
+// Substituted tiles are tinted with this color in the preview image
+const SUBSTITUTED_TILE_HIGHLIGHT_COLOR: Rgb<u8> = Rgb([255, 0, 0]);
+
+pub struct FullScreenImage {
+    // The name suffix (the VRAM address or bank), the tile index of the first tile in the 0x8000 addressing mode
+    // (the block $9000 continues after the block $8800) and the tiles of every tile data block, that contains tiles
+    pub tile_blocks: Vec<(String, usize, Vec<TileColorArray>)>,
+    pub index_array: TileIndexArray,
+    pub attribute_array: Option<AttributeByteArray>,
+    // The first line that uses the blocks $9000 and $8800 (LCDC bit 4 cleared)
+    pub lcdc_switch_line: Option<u32>,
+    // The screen with the substituted tiles highlighted, if tiles were substituted
+    pub preview_image: Option<image::RgbImage>,
+}
+
+/// Reads a 160x144 image, deduplicates its tiles and allocates them in VRAM. If use_vram_bank_1 is set (GBC),
+/// the tiles that don't fit into VRAM bank 0 are placed in VRAM bank 1 and an attribute array selects the bank.
+/// If the screen contains more distinct tiles than max_tile_count, the most similar tiles are merged (on the GBC
+/// including flipped versions, since the attributes can flip tiles).
+pub fn full_screen_image_from_path(path: &Path, color_palette: &ColorPalette, use_palette_indices: bool, aseprite_layer_selection: &AsepriteLayerSelection, color_matching_settings: Option<&ColorMatchingSettings>, quantization_settings: Option<&QuantizationSettings>, use_vram_bank_1: bool, max_tile_count: Option<usize>) -> FullScreenImage {
+    let image = palette_conforming_tile_source_image_from_path(path, use_palette_indices, aseprite_layer_selection, color_palette, color_matching_settings, quantization_settings);
+    if (image.width(), image.height()) != (SCREEN_WIDTH_IN_TILES * 8, SCREEN_HEIGHT_IN_TILES * 8) {
+        panic!(
+            "The full-screen image \"{}\" is {}x{} pixels, but it has to be the size of the screen ({}x{} pixels)",
+            path.imm_to_str(), image.width(), image.height(), SCREEN_WIDTH_IN_TILES * 8, SCREEN_HEIGHT_IN_TILES * 8
+        );
+    }
+
+    // Distinct tiles in the order of their first appearance (row by row) and the distinct tile of every position
+    let mut distinct_tiles: Vec<TileColorArray> = Vec::new();
+    let mut distinct_tile_numbers: HashMap<TileColorArray, usize> = HashMap::new();
+    let mut screen_tile_numbers = [[0; SCREEN_WIDTH_IN_TILES as usize]; SCREEN_HEIGHT_IN_TILES as usize];
+    for tile_y in 0..SCREEN_HEIGHT_IN_TILES {
+        for tile_x in 0..SCREEN_WIDTH_IN_TILES {
+            let tile = read_tile_from_tile_source_image(tile_x, tile_y, &image, color_palette);
+            let tile_number = *distinct_tile_numbers.entry(tile.clone()).or_insert_with(|| {
+                distinct_tiles.push(tile);
+                distinct_tiles.len() - 1
+            });
+            screen_tile_numbers[tile_y as usize][tile_x as usize] = tile_number;
+        }
+    }
+
+    // Tiles are only flipped if they were substituted with a flipped version of another tile
+    let mut screen_tile_flips = [[(false, false); SCREEN_WIDTH_IN_TILES as usize]; SCREEN_HEIGHT_IN_TILES as usize];
+    let mut preview_image = None;
+    if let Some(max_tile_count) = max_tile_count.filter(|x| distinct_tiles.len() > *x) {
+        if max_tile_count == 0 {
+            panic!("The maximum tile count of full-screen images has to be at least 1");
+        }
+        let mut occurrence_counts = vec![0; distinct_tiles.len()];
+        for tile_number in screen_tile_numbers.iter().flatten() {
+            occurrence_counts[*tile_number] += 1;
+        }
+        let tile_substitutions = tile_substitutions(&distinct_tiles, &occurrence_counts, max_tile_count, use_vram_bank_1);
+
+        // The remaining tiles are numbered in their previous order
+        let remaining_tile_numbers: Vec<usize> = (0..distinct_tiles.len()).filter(|x| tile_substitutions[*x].0 == *x).collect();
+        let mut preview = image::RgbImage::new(SCREEN_WIDTH_IN_TILES * 8, SCREEN_HEIGHT_IN_TILES * 8);
+        let mut substituted_position_count = 0;
+        for tile_y in 0..SCREEN_HEIGHT_IN_TILES as usize {
+            for tile_x in 0..SCREEN_WIDTH_IN_TILES as usize {
+                let original_tile_number = screen_tile_numbers[tile_y][tile_x];
+                let (tile_number, x_flip, y_flip) = tile_substitutions[original_tile_number];
+                screen_tile_numbers[tile_y][tile_x] = remaining_tile_numbers.iter().position(|x| *x == tile_number).unwrap();
+                screen_tile_flips[tile_y][tile_x] = (x_flip, y_flip);
+
+                let is_substituted = tile_number != original_tile_number;
+                substituted_position_count += is_substituted as usize;
+                let tile = flipped_tile(&distinct_tiles[tile_number], x_flip, y_flip);
+                for x in 0..8 {
+                    for y in 0..8 {
+                        let color = color_palette[tile.get(x, y) as usize];
+                        let color = match is_substituted {
+                            true => Rgb(std::array::from_fn(|i| ((color[i] as u16 + SUBSTITUTED_TILE_HIGHLIGHT_COLOR[i] as u16) / 2) as u8)),
+                            false => color,
+                        };
+                        preview.put_pixel(tile_x as u32 * 8 + x, tile_y as u32 * 8 + y, color);
+                    }
+                }
+            }
+        }
+
+        print_warning(format!(
+            "{} of the {} distinct tiles of the full-screen image \"{}\" were substituted with similar tiles to meet the maximum of {} tiles, which changes {} tiles of the screen",
+            distinct_tiles.len() - remaining_tile_numbers.len(), distinct_tiles.len(), path.imm_to_str(), max_tile_count, substituted_position_count
+        ).as_str());
+        distinct_tiles = remaining_tile_numbers.iter().map(|x| distinct_tiles[*x].clone()).collect();
+        preview_image = Some(preview);
+    }
+
+    return allocated_full_screen_image(&distinct_tiles, &screen_tile_numbers, &screen_tile_flips, use_vram_bank_1, preview_image, path);
+}
+
+// Allocates the distinct tiles of a screen in VRAM and writes the tile index (and attribute) array. On the DMG, the
+// first row at which the tiles can be split between the two addressing modes becomes the LCDC switch line.
+fn allocated_full_screen_image(
+    distinct_tiles: &Vec<TileColorArray>,
+    screen_tile_numbers: &[[usize; SCREEN_WIDTH_IN_TILES as usize]; SCREEN_HEIGHT_IN_TILES as usize],
+    screen_tile_flips: &[[(bool, bool); SCREEN_WIDTH_IN_TILES as usize]; SCREEN_HEIGHT_IN_TILES as usize],
+    use_vram_bank_1: bool,
+    preview_image: Option<image::RgbImage>,
+    path: &Path,
+) -> FullScreenImage {
+    let mut index_array: TileIndexArray = unsafe { std::mem::zeroed() };
+    let mut attribute_array: Option<AttributeByteArray> = match use_vram_bank_1 {
+        true => Some(unsafe { std::mem::zeroed() }),
+        false => None,
+    };
+    let tiles_of_tile_numbers = |tile_numbers: &Vec<usize>| -> Vec<TileColorArray> { tile_numbers.iter().map(|x| distinct_tiles[*x].clone()).collect() };
+
+    // Every bank contains 256 tiles, which are addressed with LCDC bit 4 set
+    if use_vram_bank_1 || distinct_tiles.len() <= 2 * VRAM_BLOCK_TILE_COUNT {
+        let bank_tile_count = 2 * VRAM_BLOCK_TILE_COUNT;
+        for tile_y in 0..SCREEN_HEIGHT_IN_TILES {
+            for tile_x in 0..SCREEN_WIDTH_IN_TILES {
+                let tile_number = screen_tile_numbers[tile_y as usize][tile_x as usize];
+                index_array.assign(tile_x, tile_y, (tile_number % bank_tile_count) as u8);
+                if let Some(attribute_array) = attribute_array.as_mut() {
+                    let (x_flip, y_flip) = screen_tile_flips[tile_y as usize][tile_x as usize];
+                    let vram_bank_bit = if tile_number >= bank_tile_count { ATTRIBUTE_VRAM_BANK_1 } else { 0 };
+                    attribute_array.assign(tile_x, tile_y, vram_bank_bit | attribute_byte_from_flips(x_flip, y_flip));
+                }
+            }
+        }
+
+        let tile_numbers: Vec<usize> = (0..distinct_tiles.len()).collect();
+        let tile_blocks: Vec<(String, usize, Vec<TileColorArray>)> = match use_vram_bank_1 {
+            true => vec![
+                (String::from("bank0"), 0, tiles_of_tile_numbers(&tile_numbers[..distinct_tiles.len().min(bank_tile_count)].to_vec())),
+                (String::from("bank1"), 0, tiles_of_tile_numbers(&tile_numbers[distinct_tiles.len().min(bank_tile_count)..].to_vec())),
+            ],
+            false => vec![
+                (String::from("8000"), 0, tiles_of_tile_numbers(&tile_numbers[..distinct_tiles.len().min(VRAM_BLOCK_TILE_COUNT)].to_vec())),
+                (String::from("8800"), VRAM_BLOCK_TILE_COUNT, tiles_of_tile_numbers(&tile_numbers[distinct_tiles.len().min(VRAM_BLOCK_TILE_COUNT)..].to_vec())),
+            ],
+        };
+        return FullScreenImage {
+            tile_blocks: tile_blocks.into_iter().filter(|(_, _, tiles)| !tiles.is_empty()).collect(),
+            index_array: index_array,
+            attribute_array: attribute_array,
+            lcdc_switch_line: None,
+            preview_image: preview_image,
+        };
+    }
+
+    // The rows above the split row use the blocks $8000 and $8800, the rows below use the blocks $9000 and $8800
+    for split_row in 1..SCREEN_HEIGHT_IN_TILES as usize {
+        let upper_tile_numbers: BTreeSet<usize> = screen_tile_numbers[..split_row].iter().flatten().cloned().collect();
+        let lower_tile_numbers: BTreeSet<usize> = screen_tile_numbers[split_row..].iter().flatten().cloned().collect();
+        let Some((block_8000, block_8800, block_9000)) = split_screen_tile_blocks(&upper_tile_numbers, &lower_tile_numbers) else {
+            continue;
+        };
+
+        // Tiles of the block $8800 have the indices 128-255 in both addressing modes
+        let mut upper_tile_indices: HashMap<usize, u8> = HashMap::new();
+        let mut lower_tile_indices: HashMap<usize, u8> = HashMap::new();
+        for (index, tile_number) in block_8000.iter().enumerate() {
+            upper_tile_indices.insert(*tile_number, index as u8);
+        }
+        for (index, tile_number) in block_9000.iter().enumerate() {
+            lower_tile_indices.insert(*tile_number, index as u8);
+        }
+        for (index, tile_number) in block_8800.iter().enumerate() {
+            upper_tile_indices.insert(*tile_number, (VRAM_BLOCK_TILE_COUNT + index) as u8);
+            lower_tile_indices.insert(*tile_number, (VRAM_BLOCK_TILE_COUNT + index) as u8);
+        }
+
+        for tile_y in 0..SCREEN_HEIGHT_IN_TILES {
+            for tile_x in 0..SCREEN_WIDTH_IN_TILES {
+                let tile_number = screen_tile_numbers[tile_y as usize][tile_x as usize];
+                let tile_indices = if (tile_y as usize) < split_row { &upper_tile_indices } else { &lower_tile_indices };
+                index_array.assign(tile_x, tile_y, tile_indices[&tile_number]);
+            }
+        }
+
+        return FullScreenImage {
+            tile_blocks: vec![
+                (String::from("8000"), 0, tiles_of_tile_numbers(&block_8000)),
+                (String::from("8800"), VRAM_BLOCK_TILE_COUNT, tiles_of_tile_numbers(&block_8800)),
+                (String::from("9000"), 2 * VRAM_BLOCK_TILE_COUNT, tiles_of_tile_numbers(&block_9000)),
+            ].into_iter().filter(|(_, _, tiles)| !tiles.is_empty()).collect(),
+            index_array: index_array,
+            attribute_array: None,
+            lcdc_switch_line: Some(split_row as u32 * 8),
+            preview_image: preview_image,
+        };
+    }
+
+    panic!(
+        "The {} distinct tiles of the full-screen image \"{}\" can't be split between the upper and the lower part of the screen, \
+        so that each part uses at most 256 tiles. Consider the GBC mode, which uses VRAM bank 1",
+        distinct_tiles.len(), path.imm_to_str()
+    );
+}
+
+// Returns the tile and the flips, that every tile is displayed with. The pair of most similar tiles (the fewest
+// different pixels) is merged until only max_tile_count tiles remain, the tile that is used less often is substituted.
+fn tile_substitutions(tiles: &Vec<TileColorArray>, occurrence_counts: &Vec<usize>, max_tile_count: usize, allow_flips: bool) -> Vec<(usize, bool, bool)> {
+    let flips: Vec<(bool, bool)> = match allow_flips {
+        true => vec![(false, false), (true, false), (false, true), (true, true)],
+        false => vec![(false, false)],
+    };
+    let different_pixel_count = |a: &TileColorArray, b: &TileColorArray| (0..64).filter(|i| a.get(i % 8, i / 8) != b.get(i % 8, i / 8)).count();
+
+    // The distance between the tiles i and j (i < j) and the flips of tile i, that resemble tile j the most.
+    // Flipping is symmetric, so tile i resembles tile j with the same flips.
+    let mut distances = vec![vec![(usize::MAX, false, false); tiles.len()]; tiles.len()];
+    for i in 0..tiles.len() {
+        let flipped_tiles: Vec<(TileColorArray, bool, bool)> = flips.iter().map(|(x_flip, y_flip)| (flipped_tile(&tiles[i], *x_flip, *y_flip), *x_flip, *y_flip)).collect();
+        for j in (i + 1)..tiles.len() {
+            distances[i][j] = flipped_tiles.iter()
+                .map(|(flipped_tile, x_flip, y_flip)| (different_pixel_count(flipped_tile, &tiles[j]), *x_flip, *y_flip))
+                .min_by_key(|(distance, _, _)| *distance)
+                .unwrap();
+        }
+    }
+
+    let mut res: Vec<(usize, bool, bool)> = (0..tiles.len()).map(|x| (x, false, false)).collect();
+    let mut occurrence_counts = occurrence_counts.clone();
+    let mut remaining_tile_numbers: Vec<usize> = (0..tiles.len()).collect();
+    while remaining_tile_numbers.len() > max_tile_count {
+        let (i, j) = remaining_tile_numbers.iter().enumerate()
+            .flat_map(|(position, i)| remaining_tile_numbers[(position + 1)..].iter().map(move |j| (*i, *j)))
+            .min_by_key(|(i, j)| distances[*i][*j].0)
+            .unwrap();
+        let (_, x_flip, y_flip) = distances[i][j];
+        let (kept_tile_number, substituted_tile_number) = if occurrence_counts[j] > occurrence_counts[i] { (j, i) } else { (i, j) };
+
+        // Tiles that were substituted with the substituted tile before are substituted with the kept tile now
+        for substitution in res.iter_mut().filter(|x| x.0 == substituted_tile_number) {
+            *substitution = (kept_tile_number, substitution.1 ^ x_flip, substitution.2 ^ y_flip);
+        }
+        occurrence_counts[kept_tile_number] += occurrence_counts[substituted_tile_number];
+        remaining_tile_numbers.retain(|x| *x != substituted_tile_number);
+    }
+
+    return res;
+}
+
+fn flipped_tile(tile: &TileColorArray, x_flip: bool, y_flip: bool) -> TileColorArray {
+    let mut res = tile.clone();
+    for x in 0..8 {
+        for y in 0..8 {
+            res.assign(if x_flip { 7 - x } else { x }, if y_flip { 7 - y } else { y }, tile.get(x, y));
+        }
+    }
+    return res;
+}
+
+// Allocates the tiles of the upper and the lower part of the screen in the blocks $8000, $8800 and $9000. Tiles of
+// both parts are placed in the shared block $8800, if there are too many, the remaining ones are copied into the
+// blocks $8000 and $9000. Returns None if the tiles don't fit.
+fn split_screen_tile_blocks(upper_tile_numbers: &BTreeSet<usize>, lower_tile_numbers: &BTreeSet<usize>) -> Option<(Vec<usize>, Vec<usize>, Vec<usize>)> {
+    let shared_tile_numbers: Vec<usize> = upper_tile_numbers.intersection(lower_tile_numbers).cloned().collect();
+    let upper_only_tile_numbers: Vec<usize> = upper_tile_numbers.difference(lower_tile_numbers).cloned().collect();
+    let lower_only_tile_numbers: Vec<usize> = lower_tile_numbers.difference(upper_tile_numbers).cloned().collect();
+
+    let mut block_8800: Vec<usize> = shared_tile_numbers.iter().take(VRAM_BLOCK_TILE_COUNT).cloned().collect();
+    let copied_tile_numbers: Vec<usize> = shared_tile_numbers.iter().skip(VRAM_BLOCK_TILE_COUNT).cloned().collect();
+    let mut block_8000 = copied_tile_numbers.clone();
+    let mut block_9000 = copied_tile_numbers;
+
+    for (block, tile_numbers) in [(&mut block_8000, upper_only_tile_numbers), (&mut block_9000, lower_only_tile_numbers)] {
+        for tile_number in tile_numbers {
+            if block.len() < VRAM_BLOCK_TILE_COUNT {
+                block.push(tile_number);
+            } else {
+                block_8800.push(tile_number);
+            }
+        }
+    }
+
+    match block_8000.len() <= VRAM_BLOCK_TILE_COUNT && block_8800.len() <= VRAM_BLOCK_TILE_COUNT && block_9000.len() <= VRAM_BLOCK_TILE_COUNT {
+        true => Some((block_8000, block_8800, block_9000)),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile_from_fn(palette_index: impl Fn(usize, usize) -> u8) -> TileColorArray {
+        let mut res: TileColorArray = unsafe { std::mem::zeroed() };
+        for x in 0..8 {
+            for y in 0..8 {
+                res.assign(x, y, palette_index(x, y));
+            }
+        }
+        return res;
+    }
+
+    fn different_pixel_count(a: &TileColorArray, b: &TileColorArray) -> usize {
+        return (0..64).filter(|i| a.get(i % 8, i / 8) != b.get(i % 8, i / 8)).count();
+    }
+
+    // A screen whose tile numbers are given by tile_number, with a distinct tile for every tile number
+    fn allocated_screen(tile_number: impl Fn(usize, usize) -> usize, use_vram_bank_1: bool) -> FullScreenImage {
+        let screen_tile_numbers: [[usize; SCREEN_WIDTH_IN_TILES as usize]; SCREEN_HEIGHT_IN_TILES as usize] = std::array::from_fn(|y| std::array::from_fn(|x| tile_number(x, y)));
+        let tile_count = screen_tile_numbers.iter().flatten().max().unwrap() + 1;
+        let distinct_tiles: Vec<TileColorArray> = (0..tile_count).map(|tile_number| tile_from_fn(|x, y| ((tile_number >> (x + 8 * y)) & 1) as u8)).collect();
+        let screen_tile_flips = [[(false, false); SCREEN_WIDTH_IN_TILES as usize]; SCREEN_HEIGHT_IN_TILES as usize];
+        return allocated_full_screen_image(&distinct_tiles, &screen_tile_numbers, &screen_tile_flips, use_vram_bank_1, None, Path::new("screen.png"));
+    }
+
+    fn tile_block_sizes(full_screen_image: &FullScreenImage) -> Vec<(String, usize, usize)> {
+        return full_screen_image.tile_blocks.iter().map(|(name, first_tile_index, tiles)| (name.clone(), *first_tile_index, tiles.len())).collect();
+    }
+
+    #[test]
+    fn screen_with_360_distinct_tiles_switches_the_addressing_mode() {
+        let full_screen_image = allocated_screen(|x, y| y * 20 + x, false);
+
+        // The upper 6 rows (120 tiles) fit into the block $8000, the following 128 tiles fill the block $9000 and
+        // the remaining 112 tiles of the lower part are placed in the block $8800
+        assert_eq!(full_screen_image.lcdc_switch_line, Some(48));
+        assert_eq!(tile_block_sizes(&full_screen_image), vec![(String::from("8000"), 0, 120), (String::from("8800"), 128, 112), (String::from("9000"), 256, 128)]);
+        assert_eq!(full_screen_image.index_array.get(19, 5), 119);
+        assert_eq!(full_screen_image.index_array.get(0, 6), 0);
+        assert_eq!(full_screen_image.index_array.get(7, 12), 127);
+        assert_eq!(full_screen_image.index_array.get(8, 12), 128);
+        assert_eq!(full_screen_image.index_array.get(19, 17), 239);
+        assert!(full_screen_image.attribute_array.is_none());
+    }
+
+    #[test]
+    fn screen_with_360_distinct_tiles_uses_vram_bank_1() {
+        let full_screen_image = allocated_screen(|x, y| y * 20 + x, true);
+
+        assert_eq!(full_screen_image.lcdc_switch_line, None);
+        assert_eq!(tile_block_sizes(&full_screen_image), vec![(String::from("bank0"), 0, 256), (String::from("bank1"), 0, 104)]);
+        let attribute_array = full_screen_image.attribute_array.as_ref().unwrap();
+        assert_eq!((full_screen_image.index_array.get(15, 12), attribute_array.get(15, 12)), (255, 0));
+        assert_eq!((full_screen_image.index_array.get(16, 12), attribute_array.get(16, 12)), (0, ATTRIBUTE_VRAM_BANK_1));
+        assert_eq!((full_screen_image.index_array.get(19, 17), attribute_array.get(19, 17)), (103, ATTRIBUTE_VRAM_BANK_1));
+    }
+
+    #[test]
+    fn screen_with_256_distinct_tiles_needs_no_switch() {
+        let full_screen_image = allocated_screen(|x, y| (y * 20 + x) % 256, false);
+
+        assert_eq!(full_screen_image.lcdc_switch_line, None);
+        assert_eq!(tile_block_sizes(&full_screen_image), vec![(String::from("8000"), 0, 128), (String::from("8800"), 128, 128)]);
+        assert_eq!(full_screen_image.index_array.get(15, 12), 255);
+        assert_eq!(full_screen_image.index_array.get(16, 12), 0);
+    }
+
+    #[test]
+    fn shared_tiles_beyond_the_block_8800_are_copied() {
+        // 150 tiles are used by both parts, 10 tiles only by the upper and 20 only by the lower part
+        let upper_tile_numbers: BTreeSet<usize> = (0..160).collect();
+        let lower_tile_numbers: BTreeSet<usize> = (0..150).chain(160..180).collect();
+        let (block_8000, block_8800, block_9000) = split_screen_tile_blocks(&upper_tile_numbers, &lower_tile_numbers).unwrap();
+
+        assert_eq!(block_8800, (0..128).collect::<Vec<usize>>());
+        assert_eq!(block_8000, (128..160).collect::<Vec<usize>>());
+        assert_eq!(block_9000, (128..150).chain(160..180).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn too_many_tiles_for_both_parts_dont_fit() {
+        let upper_tile_numbers: BTreeSet<usize> = (0..200).collect();
+        let lower_tile_numbers: BTreeSet<usize> = (200..400).collect();
+        assert!(split_screen_tile_blocks(&upper_tile_numbers, &lower_tile_numbers).is_none());
+    }
+
+    #[test]
+    fn tile_merged_into_a_flipped_tile_is_merged_again() {
+        // Tile 1 is the x-flipped tile 0 with one different pixel, tile 2 the y-flipped tile 1
+        let tile_0 = tile_from_fn(|x, y| ((x * 3 + y * 5 + x * y) % 4) as u8);
+        let mut tile_1 = flipped_tile(&tile_0, true, false);
+        let changed_palette_index = (tile_1.get(0, 0) + 1) % 4;
+        tile_1.assign(0, 0, changed_palette_index);
+        let tile_2 = flipped_tile(&tile_1, false, true);
+        let tiles = vec![tile_0, tile_1, tile_2.clone()];
+
+        // Tile 2 is merged into tile 1 first (y flip), then tile 1 into tile 0 (x flip)
+        let tile_substitutions = tile_substitutions(&tiles, &vec![5, 2, 1], 1, true);
+        assert_eq!(tile_substitutions, vec![(0, false, false), (0, true, false), (0, true, true)]);
+
+        // The preview displays the kept tile with the flips of the substitution, so only the changed pixel differs
+        let displayed_tiles: Vec<TileColorArray> = tile_substitutions.iter().map(|(tile_number, x_flip, y_flip)| flipped_tile(&tiles[*tile_number], *x_flip, *y_flip)).collect();
+        assert_eq!(displayed_tiles.iter().zip(tiles.iter()).map(|(a, b)| different_pixel_count(a, b)).collect::<Vec<usize>>(), vec![0, 1, 1]);
+        assert_ne!(displayed_tiles[2].get(0, 7), tile_2.get(0, 7));
+    }
+}
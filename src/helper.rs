@@ -16,6 +16,40 @@ impl ImmediateToStr for std::path::Path {
 }
 
 
+/// Turns an arbitrary name (e.g. a layer name from a map editor) into a valid c identifier
+/// by replacing every invalid character with an underscore
+pub fn c_identifier_from_str(str: &str) -> String {
+    let mut res: String = str.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' {c} else {'_'}).collect();
+    if res.is_empty() || res.chars().next().unwrap().is_ascii_digit() {
+        res.insert(0, '_');
+    }
+    return res;
+}
+
+/// Paths inside of files from map editors are relative to the file they are specified in
+pub fn path_relative_to_file(file_path: &std::path::Path, relative_path: &str) -> std::path::PathBuf {
+    match file_path.parent() {
+        Some(parent) => parent.join(relative_path),
+        None => std::path::PathBuf::from(relative_path),
+    }
+}
+
+/// Returns the path of a companion file of a map (e.g. "level_collision.png" for "level.png" and the suffix
+/// "_collision"), if it exists
+pub fn companion_path_for_map_path(map_path: &std::path::Path, suffix: &str) -> Option<std::path::PathBuf> {
+    let mut file_name = map_path.file_stem().unwrap().to_owned();
+    file_name.push(suffix);
+    if let Some(extension) = map_path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    let companion_path = map_path.with_file_name(file_name);
+    match companion_path.exists() {
+        true => Some(companion_path),
+        false => None,
+    }
+}
+
 pub fn print_warning(str: &str) {
     println!("Warning: {}", str);
 }
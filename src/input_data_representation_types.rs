@@ -6,7 +6,9 @@ pub struct ColorPalette([Rgb<u8>; 4]);
 
 pub struct TileInfo {
     pub color_array: TileColorArray,
-    pub name: Option<String>
+    pub name: Option<String>,
+    // Names of the properties (e.g. solid, damage) of the tile
+    pub properties: Vec<String>
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Deref, DerefMut)]
@@ -8,12 +8,30 @@ use std::path::{
 use std::ops::Deref;
 use std::thread::current;
 use std::vec::Vec;
-use std::{collections::{HashMap, LinkedList}, env};
+use std::{collections::{BTreeSet, HashMap, LinkedList}, env};
 use clap::Parser;
 use derive_more::{Deref, DerefMut};
 
 mod output;
 mod read_input;
+mod read_tiled;
+mod read_ldtk;
+mod read_aseprite;
+mod compression;
+mod metatiles;
+mod collision;
+mod tile_properties;
+mod banking;
+mod report;
+mod pruning;
+mod font;
+mod text;
+mod dmg_palette;
+mod quantization;
+mod color_matching;
+mod sgb_border;
+mod sgb_palettes;
+mod full_screen;
 mod cli_parser;
 mod input_data_representation_types;
 mod helper;
@@ -21,6 +39,24 @@ mod helper;
 use output::*;
 use cli_parser::*;
 use read_input::*;
+use read_tiled::*;
+use read_ldtk::*;
+use read_aseprite::*;
+use compression::*;
+use metatiles::*;
+use collision::*;
+use tile_properties::*;
+use banking::*;
+use report::*;
+use pruning::*;
+use font::*;
+use text::*;
+use dmg_palette::*;
+use quantization::*;
+use color_matching::*;
+use sgb_border::*;
+use sgb_palettes::*;
+use full_screen::*;
 use input_data_representation_types::*;
 use helper::*;
 
@@ -37,6 +73,15 @@ fn main() {
     if let Some(input_directory) = parse_result.input_directory.as_ref() {
         std::env::set_current_dir(input_directory).expect(format!("Couldn't change working directory to the specified input directory: \"{}\"", input_directory.to_str().unwrap()).as_str())
     }
+    // Decide which layers of Aseprite files are used
+    let aseprite_layer_selection = if !parse_result.aseprite_layers.is_empty() {
+        AsepriteLayerSelection::Choose(parse_result.aseprite_layers.clone())
+    } else if !parse_result.aseprite_skip_layers.is_empty() {
+        AsepriteLayerSelection::Skip(parse_result.aseprite_skip_layers.clone())
+    } else {
+        AsepriteLayerSelection::MergeVisible
+    };
+
     // Setup output vector that contains info entries for each output file
     let mut output: Output = Output(Vec::new());
 
@@ -52,25 +97,266 @@ fn main() {
     });
     
 
+    let map_compression = MapCompression {
+        compression_format: parse_result.map_compression,
+        chunking: parse_result.map_compression_chunking,
+    };
+
+    if let Some(metatile_size) = parse_result.metatile_size {
+        check_metatile_size(metatile_size);
+    }
+
+    // Maps can have companion collision images if collision colors are specified
+    let collision_settings = match parse_result.collision_colors.is_empty() {
+        true => None,
+        false => Some(CollisionSettings {
+            collision_colors: parse_result.collision_colors.clone(),
+            collision_image_suffix: parse_result.collision_image_suffix.clone(),
+        }),
+    };
+
+    // Colors of the tileset and the maps that are close to palette colors or aliases of palette indices are matched to palette colors
+    let color_matching_settings = match parse_result.color_tolerance == 0 && parse_result.color_aliases.is_empty() {
+        true => None,
+        false => Some(ColorMatchingSettings::new(parse_result.color_tolerance, parse_result.color_aliases.clone())),
+    };
+
+    // Colors of the tileset and the maps that aren't part of the palette are quantized if requested
+    let quantization_settings = match parse_result.quantize {
+        true => Some(QuantizationSettings {
+            color_distance_metric: parse_result.color_distance_metric,
+            dithering: parse_result.dithering,
+        }),
+        false => None,
+    };
+
+    // Aseprite tilesets are parsed once, their slices name tiles and their tags describe animations of tiles
+    let reference_tileset_aseprite_file = match (!parse_result.reference_tileset_path.is_dir()) && is_aseprite_path(&parse_result.reference_tileset_path) {
+        true => Some(aseprite_file_from_path(&parse_result.reference_tileset_path)),
+        false => None,
+    };
+
     // Process reference tileset file
-    let (tile_info_vec, color_palette): (Vec<TileInfo>, ColorPalette) = read_tileset_info_from_path(&parse_result.reference_tileset_path);
+    let (mut tile_info_vec, color_palette, tileset_has_reference_tile): (Vec<TileInfo>, ColorPalette, bool) = read_tileset_info_from_path(&parse_result.reference_tileset_path, reference_tileset_aseprite_file.as_ref(), parse_result.use_palette_indices, &aseprite_layer_selection, parse_result.infer_palette, &parse_result.palette_order, color_matching_settings.as_ref(), quantization_settings.as_ref());
 
-    // Write the retrieved information from the tileset to output info
-    reference_tileset_output_info.write_tileset(&tile_info_vec, parse_result.use_hex);
+    if let Some(tile_properties_file_path) = parse_result.tile_properties_file_path.as_ref() {
+        apply_tile_properties_file(tile_properties_file_path, &mut tile_info_vec);
+    }
+
+    let mut tile_animation_ranges = match reference_tileset_aseprite_file.as_ref() {
+        Some(aseprite_file) => tile_animation_ranges_from_aseprite_tags(aseprite_file, tileset_has_reference_tile),
+        None => Vec::new(),
+    };
+
+    let tile_search_map = tile_searchmap_from_tiledata_vec(&tile_info_vec);
 
-    let tile_search_map = tile_searchmap_from_tiledata_vec(tile_info_vec);
+    // Every map is read once, the tile indices of the maps are remapped when they are written if the tileset is pruned
+    let mut map_data_vec: Vec<MapData> = Vec::new();
 
     // Process tilemap arguments
     for (tilemap_image_path, allow_attributes) in parse_result.map_file_paths.iter().map(|x| (x, false)).chain( parse_result.map_with_attributes_file_paths.iter().map(|x| (x,true)) ) {
-        output.push(
-            create_output_info_for_tilemap_path(tilemap_image_path, &color_palette, &tile_search_map, allow_attributes, parse_result.use_hex)
+        map_data_vec.push(MapData::TilemapImage(
+            tilemap_image_data_from_path(tilemap_image_path, &color_palette, &tile_search_map, allow_attributes, parse_result.use_palette_indices, &aseprite_layer_selection, color_matching_settings.as_ref(), quantization_settings.as_ref(), collision_settings.as_ref(), parse_result.sgb_colored_screen_suffix.as_deref())
+        ));
+    }
+
+    // Process Tiled map arguments
+    for (tiled_map_path, allow_attributes) in parse_result.tiled_map_file_paths.iter().map(|x| (x, false)).chain( parse_result.tiled_map_with_attributes_file_paths.iter().map(|x| (x,true)) ) {
+        map_data_vec.push(MapData::TiledMap(
+            tiled_map_path.clone(),
+            tiled_layers_from_tiled_map_path(tiled_map_path, &parse_result.reference_tileset_path, tileset_has_reference_tile, allow_attributes)
+        ));
+    }
+
+    // Process LDtk project arguments, every level receives its own output file
+    for (ldtk_project_path, allow_attributes) in parse_result.ldtk_project_file_paths.iter().map(|x| (x, false)).chain( parse_result.ldtk_project_with_attributes_file_paths.iter().map(|x| (x,true)) ) {
+        map_data_vec.push(MapData::LdtkProject(
+            ldtk_project_path.clone(),
+            ldtk_project_from_path(ldtk_project_path, &parse_result.reference_tileset_path, tileset_has_reference_tile, allow_attributes)
+        ));
+    }
+
+    if let Some(color_matching_settings) = color_matching_settings.as_ref() {
+        print_color_alias_usages(color_matching_settings);
+    }
+
+    // Tiles are used if any map refers to them or if they belong to an animation
+    let mut used_tile_indices: BTreeSet<usize> = BTreeSet::new();
+    for index_array in map_data_vec.iter().flat_map(|x| x.tile_index_arrays()) {
+        for x in 0..32 {
+            for y in 0..32 {
+                used_tile_indices.insert(index_array.get(x, y) as usize);
+            }
+        }
+    }
+    for tile_animation_range in tile_animation_ranges.iter() {
+        let first_tile_index = tile_animation_range.first_tile_index as usize;
+        used_tile_indices.extend(first_tile_index..(first_tile_index + (tile_animation_range.tiles_per_frame * tile_animation_range.frame_count) as usize));
+    }
+    used_tile_indices.retain(|x| *x < tile_info_vec.len());
+
+    if !map_data_vec.is_empty() {
+        print_unused_tiles(&tile_info_vec, &used_tile_indices);
+    }
+
+    // Remove the unused tiles, the maps are written with the new tile indices
+    let mut tile_index_remap: Option<Vec<u8>> = None;
+    if parse_result.prune_tileset {
+        if map_data_vec.is_empty() {
+            print_warning("The tileset isn't pruned, since no maps were supplied that could use its tiles");
+        } else {
+            let remap = tile_index_remap_from_used_tile_indices(tile_info_vec.len(), &used_tile_indices);
+            tile_info_vec = pruned_tile_info_vec(tile_info_vec, &used_tile_indices);
+            for tile_animation_range in tile_animation_ranges.iter_mut() {
+                tile_animation_range.first_tile_index = remap[tile_animation_range.first_tile_index as usize] as u32;
+            }
+            tile_index_remap = Some(remap);
+        }
+    }
+
+    let map_output_infos: Vec<Output_info_for_a_single_file> = map_data_vec.iter()
+        .flat_map(|x| create_output_infos_for_map_data(x, parse_result.use_hex, map_compression, parse_result.metatile_size, tile_index_remap.as_ref()))
+        .collect();
+
+    if !tile_animation_ranges.is_empty() {
+        reference_tileset_output_info.write_tile_animation_ranges(&tile_animation_ranges);
+    }
+
+    // Tile data that doesn't fit into any of the available banks is split across them, if this is allowed
+    let tile_data_format = match parse_result.use_1bpp {
+        true => tile_data_format_1bpp(&tile_info_vec),
+        false => TileDataFormat::Bpp2,
+    };
+    let tile_data_size = tile_info_vec.len() * tile_data_format.bytes_per_tile();
+    let split_tile_data = parse_result.split_tile_data
+        && (!parse_result.available_rom_banks.is_empty())
+        && parse_result.available_rom_banks.iter().all(|x| x.free_space < tile_data_size);
+    if split_tile_data && parse_result.tile_data_compression != CompressionFormat::None {
+        panic!("Compressed tile data can't be split across ROM banks, since it has to be decompressed as a whole");
+    }
+
+    // Write the retrieved information from the tileset to output info
+    if split_tile_data {
+        let tile_data_parts = split_data_across_banks(tile_data_size, tile_data_format.bytes_per_tile(), &mut parse_result.available_rom_banks.clone());
+        output.extend(reference_tileset_output_info.write_split_tileset(&tile_info_vec, &tile_data_parts, parse_result.use_hex, tile_data_format));
+    } else {
+        reference_tileset_output_info.write_tileset(&tile_info_vec, parse_result.use_hex, parse_result.tile_data_compression, tile_data_format);
+    }
+
+    // The DMG shades of the palette indices are either specified by color or inferred from the luminance of the colors
+    if parse_result.dmg_shades_by_luminance {
+        reference_tileset_output_info.write_dmg_palette_registers(&dmg_shades_by_luminance(&color_palette));
+    } else if !parse_result.dmg_shade_colors.is_empty() {
+        reference_tileset_output_info.write_dmg_palette_registers(&dmg_shades_from_shade_colors(&color_palette, &parse_result.dmg_shade_colors));
+    }
+
+    if tile_info_vec.iter().any(|x| !x.properties.is_empty()) {
+        reference_tileset_output_info.write_tile_properties(&tile_info_vec, parse_result.use_hex);
+    }
+
+    // Font tilesets receive character lookup tables and an RGBDS charmap
+    let font_character_tile_indices = parse_result.font_characters_path.as_ref().map(|font_characters_path| {
+        font_character_tile_indices(&font_characters_from_path(font_characters_path), &parse_result.reference_tileset_path, &aseprite_layer_selection, tile_info_vec.len(), tileset_has_reference_tile)
+    });
+    if let Some(font_character_tile_indices) = font_character_tile_indices.as_ref() {
+        let fallback_tile_index = font_character_tile_indices.iter().find(|(x, _)| *x == ' ').map(|(_, x)| *x).unwrap_or(0);
+        reference_tileset_output_info.write_font_character_tables(font_character_tile_indices, fallback_tile_index, parse_result.use_hex);
+
+        // The glyph widths of variable width fonts are measured against the background color
+        if parse_result.variable_width_font {
+            let background_palette_index = match tile_data_format {
+                TileDataFormat::Bpp1 { background_palette_index, .. } => background_palette_index,
+                TileDataFormat::Bpp2 => 0,
+            };
+            reference_tileset_output_info.write_glyph_widths(
+                &glyph_widths(&tile_info_vec, font_character_tile_indices, background_palette_index, parse_result.vwf_empty_glyph_width),
+                parse_result.use_hex
+            );
+        }
+    }
+
+    // Process text arguments, the texts are encoded with the font
+    if let Some(font_character_tile_indices) = font_character_tile_indices.as_ref() {
+        if !parse_result.text_file_paths.is_empty() {
+            check_font_for_text_control_codes(font_character_tile_indices);
+        }
+        for text_file_path in parse_result.text_file_paths.iter() {
+            output.push(create_output_info_for_text_path(text_file_path, font_character_tile_indices, parse_result.use_hex));
+        }
+    }
+
+    // Full-screen images contain their own tile data. If tiles were substituted to meet the maximum tile count,
+    // a preview image shows the result.
+    let mut full_screen_preview_images = Vec::new();
+    for (full_screen_image_path, use_vram_bank_1) in parse_result.full_screen_image_paths.iter().map(|x| (x, false)).chain(parse_result.full_screen_image_with_attributes_paths.iter().map(|x| (x, true))) {
+        let full_screen_image = full_screen_image_from_path(
+            full_screen_image_path, &color_palette, parse_result.use_palette_indices, &aseprite_layer_selection, color_matching_settings.as_ref(), quantization_settings.as_ref(),
+            use_vram_bank_1, parse_result.full_screen_max_tile_count
         );
+        output.push(create_output_info_for_full_screen_image(full_screen_image_path, &full_screen_image, parse_result.use_hex, parse_result.tile_data_compression, map_compression));
+        if let Some(preview_image) = full_screen_image.preview_image {
+            full_screen_preview_images.push((full_screen_image_path, preview_image));
+        }
     }
 
+    if let Some(sgb_border_path) = parse_result.sgb_border_path.as_ref() {
+        output.push(create_output_info_for_sgb_border_path(sgb_border_path, parse_result.sgb_border_transparent_color, parse_result.use_hex));
+    }
 
+    output.extend(map_output_infos);
     output.push(reference_tileset_output_info);
 
-    output.write_to_disk(&parse_result.output_directory, &initial_working_directory, parse_result.mimic_relative_paths_to_input_directory);
+    output.assign_rom_banks(&parse_result.asset_rom_banks);
+    output.check_rom_data_sizes();
+
+    let bank_map = match parse_result.available_rom_banks.is_empty() {
+        true => None,
+        false => Some(output.pack_rom_banks(&parse_result.available_rom_banks, parse_result.bank_packing_strategy)),
+    };
+
+    if parse_result.print_report {
+        print!("{}", report_table_string(&output));
+    }
+    if let Some(report_json_path) = parse_result.report_json_path.as_ref() {
+        let report_json_path = initial_working_directory.join(report_json_path);
+        println!("writing to: {}", report_json_path.to_str().unwrap());
+        std::fs::write(report_json_path, report_json_string(&output)).unwrap();
+    }
+
+    output.write_to_disk(&parse_result.output_directory, &initial_working_directory, parse_result.mimic_relative_paths_to_input_directory, parse_result.generate_header_files);
+
+    // The RGBDS charmap of a font is written next to the output files
+    if let Some(font_character_tile_indices) = font_character_tile_indices.as_ref() {
+        let charmap_file_name = format!("{}_charmap.inc", parse_result.reference_tileset_path.file_stem().unwrap().to_str().unwrap());
+        let charmap_path = match parse_result.output_directory.as_ref() {
+            Some(output_directory) => output_directory.join(charmap_file_name.as_str()),
+            None => PathBuf::from(charmap_file_name.as_str()),
+        };
+        println!("writing to: {}", charmap_path.to_str().unwrap());
+        std::fs::write(charmap_path, rgbds_charmap_string(font_character_tile_indices, charmap_file_name.as_str())).unwrap();
+    }
+
+    // The bank map is written next to the output files
+    if let Some(bank_map) = bank_map {
+        print!("{}", bank_map);
+        let bank_map_path = match parse_result.output_directory.as_ref() {
+            Some(output_directory) => output_directory.join("bank_map.txt"),
+            None => PathBuf::from("bank_map.txt"),
+        };
+        println!("writing to: {}", bank_map_path.to_str().unwrap());
+        std::fs::write(bank_map_path, bank_map).unwrap();
+    }
+
+    // The previews of reduced full-screen images are written next to the output files
+    for (full_screen_image_path, preview_image) in full_screen_preview_images {
+        let preview_file_name = format!("{}_preview.png", full_screen_image_path.file_stem().unwrap().to_str().unwrap());
+        let preview_path = match parse_result.output_directory.as_ref() {
+            Some(output_directory) => output_directory.join(preview_file_name.as_str()),
+            None => PathBuf::from(preview_file_name.as_str()),
+        };
+        println!("writing to: {}", preview_path.to_str().unwrap());
+        preview_image.save(&preview_path).expect(format!("Couldn't write the preview image \"{}\"", preview_path.to_str().unwrap()).as_str());
+    }
 
 }
 
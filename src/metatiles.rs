@@ -0,0 +1,80 @@
+// This module groups the tiles of a map into NxN blocks (metatiles). Equal blocks are deduplicated, so that
+// a map can be stored as a table of metatile definitions and a much smaller map of metatile indices.
+
+use std::collections::HashMap;
+
+use crate::input_data_representation_types::*;
+
+/// The tile indices and attribute bytes of a single NxN block, row by row
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Metatile {
+    pub tile_indices: Vec<u8>,
+    pub attributes: Option<Vec<u8>>,
+}
+
+pub struct MetatileMap {
+    pub metatile_size: u32,
+    pub metatiles: Vec<Metatile>,
+    // Metatile indices of the (32/metatile_size)x(32/metatile_size) blocks, row by row
+    pub metatile_index_array: Vec<u8>,
+}
+
+impl MetatileMap {
+    pub fn width_in_metatiles(&self) -> u32 {
+        32 / self.metatile_size
+    }
+}
+
+pub fn check_metatile_size(metatile_size: u32) {
+    if metatile_size == 0 || 32 % metatile_size != 0 {
+        panic!("The metatile size needs to divide the map size of 32 tiles (e.g. 2 or 4), but is {}", metatile_size);
+    }
+}
+
+/// Groups the 32x32 tiles of a map into blocks of metatile_size x metatile_size tiles. Blocks with the same
+/// tile indices and attributes receive the same metatile index, in the order of their first appearance.
+pub fn metatile_map_from_arrays(index_array: &TileIndexArray, attribute_array: Option<&AttributeByteArray>, metatile_size: u32) -> MetatileMap {
+    check_metatile_size(metatile_size);
+
+    let width_in_metatiles = 32 / metatile_size;
+    let mut metatiles: Vec<Metatile> = Vec::new();
+    let mut metatile_indices: HashMap<Metatile, u8> = HashMap::new();
+    let mut metatile_index_array = Vec::new();
+
+    for block_y in 0..width_in_metatiles {
+        for block_x in 0..width_in_metatiles {
+            let mut metatile = Metatile {
+                tile_indices: Vec::new(),
+                attributes: attribute_array.map(|_| Vec::new()),
+            };
+            for y in (block_y * metatile_size)..((block_y + 1) * metatile_size) {
+                for x in (block_x * metatile_size)..((block_x + 1) * metatile_size) {
+                    metatile.tile_indices.push(index_array.get(x, y));
+                    if let (Some(attributes), Some(attribute_array)) = (metatile.attributes.as_mut(), attribute_array) {
+                        attributes.push(attribute_array.get(x, y));
+                    }
+                }
+            }
+
+            let metatile_index = match metatile_indices.get(&metatile) {
+                Some(metatile_index) => *metatile_index,
+                None => {
+                    if metatiles.len() > u8::MAX as usize {
+                        panic!("Map contains more than 256 different {}x{} metatiles", metatile_size, metatile_size);
+                    }
+                    let metatile_index = metatiles.len() as u8;
+                    metatile_indices.insert(metatile.clone(), metatile_index);
+                    metatiles.push(metatile);
+                    metatile_index
+                }
+            };
+            metatile_index_array.push(metatile_index);
+        }
+    }
+
+    return MetatileMap {
+        metatile_size: metatile_size,
+        metatiles: metatiles,
+        metatile_index_array: metatile_index_array,
+    };
+}
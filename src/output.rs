@@ -1,15 +1,46 @@
 use crate::input_data_representation_types::*;
 use crate::read_input::*;
+use crate::read_tiled::*;
+use crate::read_ldtk::*;
+use crate::read_aseprite::*;
+use crate::compression::*;
+use crate::metatiles::*;
+use crate::collision::*;
+use crate::tile_properties::*;
+use crate::banking::*;
+use crate::report::*;
+use crate::pruning::*;
+use crate::text::*;
+use crate::dmg_palette::*;
+use crate::quantization::*;
+use crate::color_matching::*;
+use crate::sgb_border::*;
+use crate::sgb_palettes::*;
+use crate::full_screen::*;
 
 use crate::helper::*;
 
 use derive_more::{Deref,DerefMut};
+use image::Rgb;
 use std::path::{PathBuf, Path};
 use std::collections::HashMap;
 
 pub struct Output_info_for_a_single_file { 
     pub content_string: String,
     pub specified_path: std::path::PathBuf,
+    // Declarations of the constants and arrays of content_string for the header file
+    pub header_declarations_string: String,
+    // Type, name and value of every constant. Constants of banked assets are defined in the header file instead,
+    // since other banks couldn't read them without switching to the bank of the asset.
+    pub constants: Vec<(String, String, String)>,
+    // Number of bytes of the arrays, which need to fit into a ROM bank
+    pub rom_data_size: usize,
+    // Number of bytes the arrays would need without compression
+    pub uncompressed_rom_data_size: usize,
+    pub rom_bank: Option<RomBank>,
+    // Set if the header file contains definitions that the C file lacks, so it has to be written in any case
+    pub requires_header_file: bool,
+    pub report: AssetReport,
 }
 
 
@@ -17,13 +48,79 @@ pub struct Output_info_for_a_single_file {
 pub struct Output(pub Vec<Output_info_for_a_single_file>);
 
 impl Output {
-    pub fn write_to_disk(self, output_directory: &Option<PathBuf>, working_directory: &PathBuf, mimic_relative_paths_to_input_directory: bool) {
+    /// Assigns the ROM banks to the assets they were specified for
+    pub fn assign_rom_banks(&mut self, asset_rom_banks: &Vec<AssetRomBank>) {
+        for asset_rom_bank in asset_rom_banks.iter() {
+            let Some(output_info) = self.0.iter_mut().find(|x| x.filename_without_extension_string() == asset_rom_bank.asset_name) else {
+                print_warning(format!("A ROM bank was specified for the asset \"{}\", but there is no output file with this name", asset_rom_bank.asset_name).as_str());
+                continue;
+            };
+            output_info.set_rom_bank(asset_rom_bank.rom_bank);
+        }
+    }
+
+    /// Packs the assets without a specified ROM bank into the available banks. Assets that were placed in one of
+    /// the available banks via --bank reduce its free space. Returns a bank map, that lists the assets of every bank.
+    pub fn pack_rom_banks(&mut self, available_rom_banks: &Vec<AvailableRomBank>, bank_packing_strategy: BankPackingStrategy) -> String {
+        let mut remaining_rom_banks = available_rom_banks.clone();
+        for output_info in self.0.iter() {
+            if let Some(RomBank::Fixed(bank)) = output_info.rom_bank {
+                if let Some(available_rom_bank) = remaining_rom_banks.iter_mut().find(|x| x.bank == bank) {
+                    if available_rom_bank.free_space < output_info.rom_data_size {
+                        print_warning(format!("The asset \"{}\" doesn't fit into the free space of its ROM bank {}", output_info.filename_without_extension_string(), bank).as_str());
+                    }
+                    available_rom_bank.free_space = available_rom_bank.free_space.saturating_sub(output_info.rom_data_size);
+                }
+            }
+        }
+
+        // Files without data (e.g. a tileset whose tile data was split into parts) only contain constants, which
+        // don't need a bank
+        let unplaced_assets: Vec<(String, usize)> = self.0.iter()
+            .filter(|x| x.rom_bank.is_none() && x.rom_data_size > 0)
+            .map(|x| (x.filename_without_extension_string(), x.rom_data_size))
+            .collect();
+        for (asset_name, bank) in pack_assets_into_banks(&unplaced_assets, &mut remaining_rom_banks, bank_packing_strategy) {
+            self.0.iter_mut().find(|x| x.filename_without_extension_string() == asset_name).unwrap().set_rom_bank(RomBank::Fixed(bank));
+        }
+
+        let mut bank_map = String::from("Bank map:\n");
+        for (available_rom_bank, remaining_rom_bank) in available_rom_banks.iter().zip(remaining_rom_banks.iter()) {
+            bank_map.push_str(
+                format!(
+                    "bank {}: {} of {} free bytes used\n",
+                    available_rom_bank.bank, available_rom_bank.free_space - remaining_rom_bank.free_space, available_rom_bank.free_space
+                ).as_str()
+            );
+            for output_info in self.0.iter().filter(|x| x.rom_bank == Some(RomBank::Fixed(available_rom_bank.bank))) {
+                bank_map.push_str(format!("\t{} ({} bytes)\n", output_info.filename_without_extension_string(), output_info.rom_data_size).as_str());
+            }
+        }
+
+        return bank_map;
+    }
+
+    /// Warns about assets that are too big for a single ROM bank
+    pub fn check_rom_data_sizes(&self) {
+        for output_info in self.0.iter() {
+            if output_info.rom_data_size > ROM_BANK_SIZE {
+                print_warning(format!(
+                    "The data of \"{}\" is {} bytes big and doesn't fit into a single ROM bank of {} bytes",
+                    output_info.c_file_name_string(), output_info.rom_data_size, ROM_BANK_SIZE
+                ).as_str());
+            }
+        }
+    }
+
+    /// Writes the C files and (if generate_header_files is set, the asset is placed in a ROM bank or the header
+    /// contains definitions of its own) the header files
+    pub fn write_to_disk(self, output_directory: &Option<PathBuf>, working_directory: &PathBuf, mimic_relative_paths_to_input_directory: bool, generate_header_files: bool) {
         std::env::set_current_dir(working_directory).unwrap();
 
         for output_info_for_a_single_file in self.0 {
 
             // Find relative path to output directory
-            let mut relative_path_to_output_directory = output_info_for_a_single_file.specified_path;
+            let mut relative_path_to_output_directory = output_info_for_a_single_file.specified_path.clone();
             if relative_path_to_output_directory.is_absolute() || (!mimic_relative_paths_to_input_directory){
                 relative_path_to_output_directory = PathBuf::from(relative_path_to_output_directory.file_name().unwrap());
             }
@@ -43,38 +140,429 @@ impl Output {
             if let Some(parent) = path_adjusted_for_output_directory.parent() {
                 std::fs::create_dir_all(parent).unwrap();
             }
+            if generate_header_files || output_info_for_a_single_file.rom_bank.is_some() || output_info_for_a_single_file.requires_header_file {
+                let header_file_path = path_adjusted_for_output_directory.with_extension("h");
+                println!("writing to: {}", header_file_path.to_str().unwrap());
+                std::fs::write(header_file_path, output_info_for_a_single_file.header_file_string()).unwrap();
+            }
+
             println!("writing to: {}", path_adjusted_for_output_directory.to_str().unwrap());
-            std::fs::write(path_adjusted_for_output_directory, output_info_for_a_single_file.content_string).unwrap();
+            std::fs::write(path_adjusted_for_output_directory, output_info_for_a_single_file.c_file_string()).unwrap();
         }
     }
 }
 
-pub fn create_output_info_for_tilemap_path(
-    tilemap_image_path: &Path, 
+/// A map image with the arrays of its companion collision image and colored screen
+pub struct TilemapImageData {
+    pub path: PathBuf,
+    pub index_array: TileIndexArray,
+    pub attribute_array: Option<AttributeByteArray>,
+    pub collision_array: Option<TilemapByteArray>,
+    pub sgb_colorization: Option<SgbColorization>,
+}
+
+/// The maps of a map argument. Maps are read once before the tileset is written, since pruning the tileset
+/// needs the tile indices of every map, and are written afterwards.
+pub enum MapData {
+    TilemapImage(TilemapImageData),
+    // Path and layers of a Tiled map
+    TiledMap(PathBuf, Vec<TiledLayer>),
+    // Path and levels of a LDtk project
+    LdtkProject(PathBuf, LdtkProject),
+}
+
+impl MapData {
+    /// Returns the tile index arrays of all maps
+    pub fn tile_index_arrays(&self) -> Vec<&TileIndexArray> {
+        match self {
+            MapData::TilemapImage(tilemap_image_data) => vec![&tilemap_image_data.index_array],
+            MapData::TiledMap(_, tiled_layers) => tiled_layers.iter().map(|x| &x.index_array).collect(),
+            MapData::LdtkProject(_, ldtk_project) => ldtk_project.levels.iter().flat_map(|x| x.tile_layers.iter().map(|x| &x.index_array)).collect(),
+        }
+    }
+}
+
+/// Reads a map image and its companion collision image and colored screen
+pub fn tilemap_image_data_from_path(
+    tilemap_image_path: &Path,
     reference_color_palette: &ColorPalette,
     tile_search_map: &HashMap<TileColorArray, TileSearchmapValue> ,
-    allow_attributes_and_generate_attribute_array: bool, 
+    allow_attributes_and_generate_attribute_array: bool,
+    use_palette_indices: bool,
+    aseprite_layer_selection: &AsepriteLayerSelection,
+    color_matching_settings: Option<&ColorMatchingSettings>,
+    quantization_settings: Option<&QuantizationSettings>,
+    collision_settings: Option<&CollisionSettings>,
+    sgb_colored_screen_suffix: Option<&str>,
+) -> TilemapImageData {
+    let (index_array, attribute_array, tilemap_image): (TileIndexArray, Option<AttributeByteArray>, TileSourceImage) = index_and_attribute_array_from_tilemap_image_path(tilemap_image_path, &reference_color_palette, &tile_search_map, allow_attributes_and_generate_attribute_array, use_palette_indices, aseprite_layer_selection, color_matching_settings, quantization_settings);
+
+    // The collision array is aligned with the tile index array
+    let collision_array = collision_settings.and_then(|collision_settings| {
+        match collision_image_path_for_map_path(tilemap_image_path, collision_settings) {
+            Some(collision_image_path) => Some(collision_array_from_image_path(&collision_image_path, tilemap_image_path, (tilemap_image.width(), tilemap_image.height()), aseprite_layer_selection, collision_settings)),
+            None => {
+                print_warning(format!(
+                    "The map \"{}\" has no collision image (with the suffix \"{}\"), so no collision array is generated for it",
+                    tilemap_image_path.imm_to_str(), collision_settings.collision_image_suffix
+                ).as_str());
+                None
+            },
+        }
+    });
+
+    // The colored version of a screen generates the SGB packets that colorize it
+    let sgb_colorization = sgb_colored_screen_suffix.and_then(|x| companion_path_for_map_path(tilemap_image_path, x)).map(|colored_screen_path| {
+        sgb_colorization_from_images(&tilemap_image, reference_color_palette, tilemap_image_path, &colored_screen_path, aseprite_layer_selection)
+    });
+
+    return TilemapImageData {
+        path: tilemap_image_path.to_path_buf(),
+        index_array: index_array,
+        attribute_array: attribute_array,
+        collision_array: collision_array,
+        sgb_colorization: sgb_colorization,
+    };
+}
+
+/// Creates the output files of the maps of a map argument. If the tileset was pruned, the tile indices are
+/// remapped with tile_index_remap.
+pub fn create_output_infos_for_map_data(
+    map_data: &MapData,
+    use_hex_notation: bool,
+    map_compression: MapCompression,
+    metatile_size: Option<u32>,
+    tile_index_remap: Option<&Vec<u8>>,
+) -> Vec<Output_info_for_a_single_file> {
+    match map_data {
+        MapData::TilemapImage(tilemap_image_data) => vec![create_output_info_for_tilemap_image_data(tilemap_image_data, use_hex_notation, map_compression, metatile_size, tile_index_remap)],
+        MapData::TiledMap(tiled_map_path, tiled_layers) => vec![create_output_info_for_tiled_map(tiled_map_path, tiled_layers, use_hex_notation, map_compression, metatile_size, tile_index_remap)],
+        MapData::LdtkProject(ldtk_project_path, ldtk_project) => create_output_infos_for_ldtk_project(ldtk_project_path, ldtk_project, use_hex_notation, map_compression, metatile_size, tile_index_remap),
+    }
+}
+
+pub fn create_output_info_for_tilemap_image_data(
+    tilemap_image_data: &TilemapImageData,
     use_hex_notation: bool,
+    map_compression: MapCompression,
+    metatile_size: Option<u32>,
+    tile_index_remap: Option<&Vec<u8>>,
 ) -> Output_info_for_a_single_file {
-    let (index_array, attribute_array): (TileIndexArray, Option<AttributeByteArray>) = index_and_attribute_array_from_tilemap_image_path(tilemap_image_path, &reference_color_palette, &tile_search_map, allow_attributes_and_generate_attribute_array);
-    let mut output_info = Output_info_for_a_single_file::new(tilemap_image_path);
+    let mut output_info = Output_info_for_a_single_file::new(tilemap_image_data.path.as_path());
+    let array_name_prefix = output_info.filename_without_extension_string();
 
-    output_info.write_tile_index_array(&index_array, use_hex_notation);
+    output_info.write_map_arrays(&array_name_prefix, &tilemap_image_data.index_array, tilemap_image_data.attribute_array.as_ref(), use_hex_notation, map_compression, metatile_size, tile_index_remap);
 
-    if let Some(attribute_byte_array) = attribute_array {
-        output_info.write_attribute_byte_array(&attribute_byte_array, use_hex_notation);
+    // The collision array is aligned with the tile index array (or the metatile map)
+    if let Some(collision_array) = tilemap_image_data.collision_array.as_ref() {
+        match metatile_size {
+            Some(metatile_size) => output_info.write_maybe_compressed_byte_array(
+                format!("{}_metatile_collision_map", array_name_prefix).as_str(),
+                &metatile_collision_array(collision_array, metatile_size),
+                use_hex_notation,
+                map_compression.compression_format
+            ),
+            None => output_info.write_tilemap_byte_array(
+                format!("{}_collision_array", array_name_prefix).as_str(),
+                collision_array,
+                use_hex_notation,
+                map_compression
+            ),
+        }
+    }
+
+    if let Some(sgb_colorization) = tilemap_image_data.sgb_colorization.as_ref() {
+        output_info.write_sgb_packets(sgb_colorization, use_hex_notation);
     }
 
     return output_info;
 }
 
 
+pub fn create_output_info_for_tiled_map(
+    tiled_map_path: &Path,
+    tiled_layers: &Vec<TiledLayer>,
+    use_hex_notation: bool,
+    map_compression: MapCompression,
+    metatile_size: Option<u32>,
+    tile_index_remap: Option<&Vec<u8>>,
+) -> Output_info_for_a_single_file {
+    let mut output_info = Output_info_for_a_single_file::new(tiled_map_path);
+    let filename_without_extension = output_info.filename_without_extension_string();
+
+    // Every layer receives its own arrays, prefixed by the file name and the layer name
+    for tiled_layer in tiled_layers.iter() {
+        let array_name_prefix = format!("{}_{}", filename_without_extension, c_identifier_from_str(tiled_layer.name.as_str()));
+
+        output_info.write_map_arrays(&array_name_prefix, &tiled_layer.index_array, tiled_layer.attribute_array.as_ref(), use_hex_notation, map_compression, metatile_size, tile_index_remap);
+    }
+
+    return output_info;
+}
+
+/// Creates an output file for every level of the LDtk project at ldtk_project_path
+pub fn create_output_infos_for_ldtk_project(
+    ldtk_project_path: &Path,
+    ldtk_project: &LdtkProject,
+    use_hex_notation: bool,
+    map_compression: MapCompression,
+    metatile_size: Option<u32>,
+    tile_index_remap: Option<&Vec<u8>>,
+) -> Vec<Output_info_for_a_single_file> {
+    let project_name = c_identifier_from_str(ldtk_project_path.file_stem().unwrap().to_str().unwrap());
+
+    let mut res = Vec::new();
+    for level in ldtk_project.levels.iter() {
+        let level_file_name = format!("{}_{}", project_name, c_identifier_from_str(level.name.as_str()));
+        let mut output_info = Output_info_for_a_single_file::new(ldtk_project_path.with_file_name(level_file_name.as_str()));
+
+        for tile_layer in level.tile_layers.iter() {
+            let array_name_prefix = format!("{}_{}", level_file_name, c_identifier_from_str(tile_layer.name.as_str()));
+
+            output_info.write_map_arrays(&array_name_prefix, &tile_layer.index_array, tile_layer.attribute_array.as_ref(), use_hex_notation, map_compression, metatile_size, tile_index_remap);
+        }
+
+        for int_grid_layer in level.int_grid_layers.iter() {
+            output_info.write_tilemap_byte_array(
+                format!("{}_{}_intgrid_array", level_file_name, c_identifier_from_str(int_grid_layer.name.as_str())).as_str(),
+                &int_grid_layer.value_array,
+                use_hex_notation,
+                map_compression
+            );
+        }
+
+        if !level.entity_layers.is_empty() {
+            output_info.write_ldtk_entity_definitions(&project_name, &ldtk_project.entity_type_names);
+        }
+        for (layer_name, entities) in level.entity_layers.iter() {
+            output_info.write_ldtk_entities(
+                format!("{}_{}", level_file_name, c_identifier_from_str(layer_name.as_str())).as_str(),
+                entities
+            );
+        }
+
+        res.push(output_info);
+    }
+
+    return res;
+}
+
+/// Creates the output file of a text file, that contains every text encoded into the tile indices of the font
+pub fn create_output_info_for_text_path(text_file_path: &Path, character_tile_indices: &Vec<(char, u8)>, use_hex_notation: bool) -> Output_info_for_a_single_file {
+    let game_texts = game_texts_from_path(text_file_path);
+    let mut output_info = Output_info_for_a_single_file::new(text_file_path);
+    let array_name_prefix = output_info.filename_without_extension_string();
+
+    // Different names can turn into the same C identifier, which would define the same array twice
+    let mut game_text_names_by_array_name: HashMap<String, &str> = HashMap::new();
+    for game_text in game_texts.iter() {
+        let array_name = format!("{}_{}", array_name_prefix, c_identifier_from_str(game_text.name.as_str()));
+        if let Some(other_name) = game_text_names_by_array_name.insert(array_name.clone(), game_text.name.as_str()) {
+            panic!(
+                "The texts \"{}\" and \"{}\" of the text file \"{}\" have the same array name {}, rename one of them",
+                other_name, game_text.name, text_file_path.imm_to_str(), array_name
+            );
+        }
+    }
+
+    output_info.write_text_control_codes();
+    for game_text in game_texts.iter() {
+        output_info.content_string.push_str(format!("// {:?}\n", game_text.text).as_str());
+        output_info.write_byte_array(
+            format!("{}_{}", array_name_prefix, c_identifier_from_str(game_text.name.as_str())).as_str(),
+            &encode_game_text(game_text, character_tile_indices, text_file_path),
+            use_hex_notation
+        );
+    }
+
+    return output_info;
+}
+
+/// Writes the tile data of every VRAM block (or bank) that a full-screen image uses and its map. If the screen
+/// is split between two addressing modes, the line at which LCDC bit 4 has to be cleared is written as well.
+pub fn create_output_info_for_full_screen_image(
+    full_screen_image_path: &Path,
+    full_screen_image: &FullScreenImage,
+    use_hex_notation: bool,
+    tile_data_compression: CompressionFormat,
+    map_compression: MapCompression,
+) -> Output_info_for_a_single_file {
+    let mut output_info = Output_info_for_a_single_file::new(full_screen_image_path);
+    let array_name_prefix = output_info.filename_without_extension_string();
+
+    if let Some(lcdc_switch_line) = full_screen_image.lcdc_switch_line {
+        output_info.content_string.push_str(
+            "// The screen uses more than 256 tiles: the lines above this line use the tile data at $8000-$8FFF (LCDC bit 4 set),\n\
+            // the following lines the tile data at $8800-$97FF. Clear LCDC bit 4 before this line is drawn (e.g. in a LYC interrupt\n\
+            // for the previous line) and set it again during the VBlank.\n"
+        );
+        output_info.write_constant("unsigned char", format!("{}_lcdc_switch_line", array_name_prefix).as_str(), lcdc_switch_line);
+        output_info.content_string.push_str("\n");
+    }
+
+    for (block_name, first_tile_index, tiles) in full_screen_image.tile_blocks.iter() {
+        let tile_info_vec: Vec<TileInfo> = tiles.iter().map(|x| TileInfo {
+            color_array: x.clone(),
+            name: None,
+            properties: Vec::new(),
+        }).collect();
+        output_info.write_tile_data(format!("{}_tile_data_{}", array_name_prefix, block_name).as_str(), &tile_info_vec, *first_tile_index, use_hex_notation, tile_data_compression, TileDataFormat::Bpp2);
+    }
+
+    let rom_data_size_before_map = output_info.rom_data_size;
+    output_info.write_tile_index_array(&array_name_prefix, &full_screen_image.index_array, use_hex_notation, map_compression);
+    if let Some(attribute_array) = full_screen_image.attribute_array.as_ref() {
+        output_info.write_attribute_byte_array(&array_name_prefix, attribute_array, use_hex_notation, map_compression);
+    }
+    output_info.report.map_data_size += output_info.rom_data_size - rom_data_size_before_map;
+
+    return output_info;
+}
+
+/// Writes the tile data, the tilemap and the palettes of an SGB border as byte arrays, that can be passed
+/// to GBDK's set_sgb_border
+pub fn create_output_info_for_sgb_border_path(sgb_border_path: &Path, transparent_color: Option<Rgb<u8>>, use_hex_notation: bool) -> Output_info_for_a_single_file {
+    let sgb_border = sgb_border_from_path(sgb_border_path, transparent_color);
+    let mut output_info = Output_info_for_a_single_file::new(sgb_border_path);
+    let array_name_prefix = output_info.filename_without_extension_string();
+
+    output_info.report.tile_count = sgb_border.tile_count;
+    output_info.report.tile_data_size = sgb_border.tile_data.len();
+    output_info.report.map_data_size = sgb_border.tilemap.len();
+
+    output_info.write_constant("size_t", format!("{}_tile_count", array_name_prefix).as_str(), sgb_border.tile_count);
+    output_info.content_string.push_str("// 4bpp SNES tile data (32 bytes per tile)\n");
+    output_info.write_byte_array(format!("{}_tile_data", array_name_prefix).as_str(), &sgb_border.tile_data, use_hex_notation);
+    output_info.content_string.push_str("// 32x28 little endian SNES attribute words (tile number, palette 4-6, flips)\n");
+    output_info.write_byte_array(format!("{}_tilemap", array_name_prefix).as_str(), &sgb_border.tilemap, use_hex_notation);
+    output_info.content_string.push_str("// Little endian BGR555 colors of the palettes 4-6 (16 colors each, the color 0 is transparent)\n");
+    output_info.write_byte_array(format!("{}_palettes", array_name_prefix).as_str(), &sgb_border.palettes, use_hex_notation);
+
+    return output_info;
+}
+
+/// How the pixels of tiles are encoded
+#[derive(Clone, Copy)]
+pub enum TileDataFormat {
+    Bpp2,
+    // Tiles with only two colors, a pixel is 1 if it has the foreground palette index (GBDK's set_bkg_1bpp_data)
+    Bpp1 {
+        background_palette_index: u8,
+        foreground_palette_index: u8,
+    },
+}
+
+impl TileDataFormat {
+    pub fn bytes_per_tile(&self) -> usize {
+        match self {
+            TileDataFormat::Bpp2 => 16,
+            TileDataFormat::Bpp1 { .. } => 8,
+        }
+    }
+}
+
+/// Returns the 1bpp tile data format for tiles that only use two palette indices in total. The lower palette
+/// index becomes the background and the higher one the foreground color.
+pub fn tile_data_format_1bpp(tiledata_vec: &Vec<TileInfo>) -> TileDataFormat {
+    let mut used_palette_indices: Vec<u8> = Vec::new();
+    for (tile_index, tile_info) in tiledata_vec.iter().enumerate() {
+        for x in 0..8 {
+            for y in 0..8 {
+                let palette_index = tile_info.color_array.get(x, y);
+                if used_palette_indices.contains(&palette_index) {
+                    continue;
+                }
+                if used_palette_indices.len() == 2 {
+                    panic!(
+                        "1bpp tiles can only use two palette indices, but the tile {}{} uses the palette index {} in addition to the palette indices {} and {} of the tileset",
+                        tile_index, tile_info.name.as_ref().map(|x| format!(" ({})", x)).unwrap_or_default(), palette_index, used_palette_indices[0], used_palette_indices[1]
+                    );
+                }
+                used_palette_indices.push(palette_index);
+            }
+        }
+    }
+    if used_palette_indices.is_empty() {
+        panic!("1bpp tile data can't be generated for an empty tileset");
+    }
+    used_palette_indices.sort();
+
+    // A tileset with a single color uses it as the background, its tiles only contain cleared bits. The
+    // foreground is the palette index 3 (or 0 if the background is 3), so it differs from the background
+    let background_palette_index = used_palette_indices[0];
+    let foreground_palette_index = match used_palette_indices.get(1) {
+        Some(palette_index) => *palette_index,
+        None => if background_palette_index == 3 { 0 } else { 3 },
+    };
+
+    return TileDataFormat::Bpp1 {
+        background_palette_index: background_palette_index,
+        foreground_palette_index: foreground_palette_index,
+    };
+}
+
+/// Encodes tiles in the given tile data format
+pub fn tile_data_bytes(tiledata_vec: &[TileInfo], tile_data_format: TileDataFormat) -> Vec<u8> {
+    match tile_data_format {
+        TileDataFormat::Bpp2 => tile_data_bytes_2bpp(tiledata_vec),
+        TileDataFormat::Bpp1 { foreground_palette_index, .. } => tile_data_bytes_1bpp(tiledata_vec, foreground_palette_index),
+    }
+}
+
+/// Encodes tiles in the 1bpp format: every line of a tile is stored in a single byte, whose bits are set
+/// for the pixels with the foreground palette index
+pub fn tile_data_bytes_1bpp(tiledata_vec: &[TileInfo], foreground_palette_index: u8) -> Vec<u8> {
+    let mut res = Vec::with_capacity(tiledata_vec.len() * 8);
+    for tile_info in tiledata_vec.iter() {
+        for y in 0..8 {
+            let mut byte: u8 = 0;
+            for x in 0..8 {
+                if tile_info.color_array.get(x, y) == foreground_palette_index {
+                    byte |= 0x80 >> x;
+                }
+            }
+            res.push(byte);
+        }
+    }
+    return res;
+}
+
+/// Encodes tiles in the 2bpp format of the gameboy: every line of a tile is stored in 2 bytes, the first byte
+/// contains the least significant bits of the palette indices and the second byte the most significant bits
+pub fn tile_data_bytes_2bpp(tiledata_vec: &[TileInfo]) -> Vec<u8> {
+    let mut res = Vec::with_capacity(tiledata_vec.len() * 16);
+    for tile_info in tiledata_vec.iter() {
+        for y in 0..8 {
+            let mut first_byte: u8 = 0;
+            let mut second_byte: u8 = 0;
+
+            for x in 0..8 {
+                let palette_index = tile_info.color_array.get(x,y);
+                first_byte += ((palette_index & 1u8) << 7) >> x;
+                second_byte += (((palette_index & 2u8) >> 1) << 7) >> x;
+            }
+
+            res.push(first_byte);
+            res.push(second_byte);
+        }
+    }
+    return res;
+}
+
+
 impl Output_info_for_a_single_file {
     pub fn new<T>(specified_path: T) -> Self where T:Into<PathBuf>{
 
         let mut res = Self {
             content_string: String::new(),
             specified_path: specified_path.into(),
+            header_declarations_string: String::new(),
+            constants: Vec::new(),
+            rom_data_size: 0,
+            uncompressed_rom_data_size: 0,
+            rom_bank: None,
+            requires_header_file: false,
+            report: AssetReport::default(),
         };
         res.write_header();
         return res;
@@ -87,7 +575,7 @@ impl Output_info_for_a_single_file {
     fn c_file_name_string(&self) -> String {
         return self.c_file_name_osstring().into_string().unwrap();
     }
-    fn filename_without_extension_string(&self) -> String{
+    pub fn filename_without_extension_string(&self) -> String{
         self.specified_path.file_stem().unwrap().to_owned().into_string().unwrap()
     }
     /// Takes the specified path and modifies the extension
@@ -117,89 +605,486 @@ impl Output_info_for_a_single_file {
         );
     }
 
-    pub fn write_attribute_byte_array(&mut self, attributes_array: &AttributeByteArray, use_hex_notation: bool) {
+    /// Places the asset in a ROM bank. The banking annotations are inserted after the header, since
+    /// "#pragma bank" has to come before the data.
+    pub fn set_rom_bank(&mut self, rom_bank: RomBank) {
+        if let Some(previous_rom_bank) = self.rom_bank {
+            panic!("The asset \"{}\" was placed in the ROM banks {:?} and {:?}", self.filename_without_extension_string(), previous_rom_bank, rom_bank);
+        }
+        let annotations = format!(
+            "#pragma bank {}\n\
+            #include <gbdk/platform.h>\n\n\
+            BANKREF({})\n\n",
+            rom_bank.pragma_bank_number(), c_identifier_from_str(self.filename_without_extension_string().as_str())
+        );
+        let header_end = self.content_string.find('\n').unwrap() + 1;
+        self.content_string.insert_str(header_end, annotations.as_str());
+        self.rom_bank = Some(rom_bank);
+    }
+
+    /// Returns the content of the header file, that declares the constants and arrays of the C file
+    pub fn header_file_string(&self) -> String {
+        let include_guard = format!("ITGBA_{}_H", c_identifier_from_str(self.filename_without_extension_string().as_str()).to_uppercase());
+        let mut res = format!(
+            "// {}.h - Generated file by ITGBA \n\
+            #ifndef {1}\n\
+            #define {1}\n\n\
+            #include <stddef.h>\n",
+            self.filename_without_extension_string(), include_guard
+        );
+        if self.rom_bank.is_some() {
+            res.push_str(format!("#include <gbdk/platform.h>\n\nBANKREF_EXTERN({})\n", c_identifier_from_str(self.filename_without_extension_string().as_str())).as_str());
+        }
+        res.push_str("\n");
+        match self.rom_bank {
+            Some(_) => {
+                for line in self.header_declarations_string.split_inclusive('\n') {
+                    match self.constants.iter().find(|(c_type, name, _)| line == format!("extern const {} {};\n", c_type, name)) {
+                        Some((_, name, value)) => res.push_str(format!("#define {} {}\n", name, value).as_str()),
+                        None => res.push_str(line),
+                    }
+                }
+            },
+            None => res.push_str(self.header_declarations_string.as_str()),
+        }
+        res.push_str(format!("\n#endif // {}\n", include_guard).as_str());
+        return res;
+    }
+
+    /// Returns the content of the C file. The constants of banked assets are left out, since the header file
+    /// defines them.
+    pub fn c_file_string(&self) -> String {
+        if self.rom_bank.is_none() {
+            return self.content_string.clone();
+        }
+        return self.content_string.split_inclusive('\n')
+            .filter(|line| !self.constants.iter().any(|(c_type, name, value)| *line == format!("const {} {} = {};\n", c_type, name, value)))
+            .collect();
+    }
+
+    /// Writes a constant and its declaration
+    pub fn write_constant(&mut self, c_type: &str, name: &str, value: impl std::fmt::Display) {
+        self.content_string.push_str(format!("const {} {} = {};\n", c_type, name, value).as_str());
+        self.header_declarations_string.push_str(format!("extern const {} {};\n", c_type, name).as_str());
+        self.constants.push((c_type.to_string(), name.to_string(), value.to_string()));
+    }
+
+    /// Writes the beginning of an array definition and its declaration. The elements and the closing brace
+    /// have to be written by the caller.
+    pub fn write_array_start(&mut self, c_type: &str, name: &str, size_in_bytes: usize) {
+        self.content_string.push_str(format!("const {} {}[] = {{\n", c_type, name).as_str());
+        self.header_declarations_string.push_str(format!("extern const {} {}[];\n", c_type, name).as_str());
+        self.rom_data_size += size_in_bytes;
+        self.uncompressed_rom_data_size += size_in_bytes;
+    }
+
+    /// Writes the tile index array and the attribute array (if there is one) of a map. If a metatile size is
+    /// given, the map is written as a table of metatiles and a map of metatile indices instead.
+    /// If the tileset was pruned, the tile indices are remapped with tile_index_remap first.
+    pub fn write_map_arrays(&mut self, array_name_prefix: &str, index_array: &TileIndexArray, attribute_array: Option<&AttributeByteArray>, use_hex_notation: bool, map_compression: MapCompression, metatile_size: Option<u32>, tile_index_remap: Option<&Vec<u8>>) {
+        let remapped_index_array;
+        let index_array = match tile_index_remap {
+            Some(tile_index_remap) => {
+                remapped_index_array = remapped_tile_index_array(index_array, tile_index_remap);
+                &remapped_index_array
+            },
+            None => index_array,
+        };
+
+        let rom_data_size_before_map = self.rom_data_size;
+        for x in 0..32 {
+            for y in 0..32 {
+                self.report.used_tile_indices.insert(index_array.get(x, y) as usize);
+                if let Some(attribute_array) = attribute_array {
+                    self.report.used_palettes.insert(attribute_array.get(x, y) & 0x07);
+                }
+            }
+        }
+
+        if let Some(metatile_size) = metatile_size {
+            self.write_metatile_map(array_name_prefix, &metatile_map_from_arrays(index_array, attribute_array, metatile_size), use_hex_notation, map_compression);
+        } else {
+            self.write_tile_index_array(array_name_prefix, index_array, use_hex_notation, map_compression);
+
+            if let Some(attribute_byte_array) = attribute_array {
+                self.write_attribute_byte_array(array_name_prefix, attribute_byte_array, use_hex_notation, map_compression);
+            }
+        }
+
+        self.report.map_data_size += self.rom_data_size - rom_data_size_before_map;
+    }
+
+    /// Writes the metatile definitions (tile indices and attributes of every metatile, row by row) and the map of
+    /// metatile indices. Only the metatile index map is compressed, since the metatiles are usually accessed randomly.
+    pub fn write_metatile_map(&mut self, array_name_prefix: &str, metatile_map: &MetatileMap, use_hex_notation: bool, map_compression: MapCompression) {
         self.content_string.push_str(
-            format!(
-                "const unsigned char {}_tilemap_attribute_array",
-                self.filename_without_extension_string()
-            )
-            .as_str(),
+            format!("// {0}x{0} metatiles, the tile indices (and attributes) of every metatile are stored row by row\n", metatile_map.metatile_size).as_str()
+        );
+        self.write_constant("size_t", format!("{}_metatile_count", array_name_prefix).as_str(), metatile_map.metatiles.len());
+        self.write_constant("size_t", format!("{}_metatile_map_width", array_name_prefix).as_str(), metatile_map.width_in_metatiles());
+
+        let metatile_tile_indices: Vec<u8> = metatile_map.metatiles.iter().flat_map(|x| x.tile_indices.iter().cloned()).collect();
+        self.write_byte_array(format!("{}_metatile_tile_indices", array_name_prefix).as_str(), &metatile_tile_indices, use_hex_notation);
+
+        if metatile_map.metatiles.iter().all(|x| x.attributes.is_some()) {
+            let metatile_attributes: Vec<u8> = metatile_map.metatiles.iter().flat_map(|x| x.attributes.as_ref().unwrap().iter().cloned()).collect();
+            self.write_byte_array(format!("{}_metatile_attributes", array_name_prefix).as_str(), &metatile_attributes, use_hex_notation);
+        }
+
+        self.write_maybe_compressed_byte_array(
+            format!("{}_metatile_map", array_name_prefix).as_str(),
+            &metatile_map.metatile_index_array,
+            use_hex_notation,
+            map_compression.compression_format
         );
-        self.write_tilemap_byte_array(&attributes_array.0, use_hex_notation);
 
+        // Every metatile index replaces the tile indices (and attributes) of metatile_size*metatile_size tiles
+        let arrays_per_tile = if metatile_map.metatiles.iter().all(|x| x.attributes.is_some()) { 2 } else { 1 };
+        self.report.metatile_count += metatile_map.metatiles.len();
+        self.report.metatile_replaced_map_data_size += metatile_map.metatile_index_array.len() * (metatile_map.metatile_size * metatile_map.metatile_size) as usize * arrays_per_tile;
     }
-    pub fn write_tile_index_array(&mut self, index_array: &TileIndexArray, use_hex_notation: bool) {
+
+    /// Writes the attribute array of a map. The array name is prefixed by array_name_prefix, which
+    /// should be the file name for files that only contain a single map.
+    pub fn write_attribute_byte_array(&mut self, array_name_prefix: &str, attributes_array: &AttributeByteArray, use_hex_notation: bool, map_compression: MapCompression) {
+        self.write_tilemap_byte_array(
+            format!("{}_tilemap_attribute_array", array_name_prefix).as_str(),
+            &attributes_array.0,
+            use_hex_notation,
+            map_compression
+        );
+    }
+    /// Writes the tile index array of a map. The array name is prefixed by array_name_prefix, which
+    /// should be the file name for files that only contain a single map.
+    pub fn write_tile_index_array(&mut self, array_name_prefix: &str, index_array: &TileIndexArray, use_hex_notation: bool, map_compression: MapCompression) {
+        self.write_tilemap_byte_array(
+            format!("{}_tileindex__array", array_name_prefix).as_str(),
+            &index_array.0,
+            use_hex_notation,
+            map_compression
+        );
+    }
+
+    /// Writes the entity struct type and a constant for every entity type of a LDtk project. The constants are
+    /// only written to the header. Since every level file contains them, the struct definition is guarded.
+    pub fn write_ldtk_entity_definitions(&mut self, project_name: &str, entity_type_names: &Vec<String>) {
+        let struct_definition = String::from(
+            "#ifndef ITGBA_LDTK_ENTITY\n\
+            #define ITGBA_LDTK_ENTITY\n\
+            // Position of an entity placed in a LDtk level\n\
+            typedef struct {\n\
+            \tunsigned char type;\n\
+            \tunsigned char tile_x;\n\
+            \tunsigned char tile_y;\n\
+            \tunsigned int pixel_x;\n\
+            \tunsigned int pixel_y;\n\
+            } ldtk_entity;\n\
+            #endif\n\n"
+        );
+        // The header needs the struct definition as well, to declare the entity arrays
+        self.content_string.push_str(struct_definition.as_str());
+        self.header_declarations_string.push_str(struct_definition.as_str());
+
+        for (type_index, entity_type_name) in entity_type_names.iter().enumerate() {
+            self.header_declarations_string.push_str(
+                format!("#define {}_ENTITY_TYPE_{} {}\n", project_name, c_identifier_from_str(entity_type_name.as_str()), type_index).as_str()
+            );
+        }
+        self.header_declarations_string.push_str("\n");
+        self.requires_header_file = true;
+    }
+
+    /// Writes the entities of a LDtk entity layer as an array of ldtk_entity structs and its length
+    pub fn write_ldtk_entities(&mut self, array_name_prefix: &str, entities: &Vec<LdtkEntity>) {
+        self.write_constant("unsigned char", format!("{}_entity_count", array_name_prefix).as_str(), entities.len());
+        // C doesn't allow empty arrays
+        if entities.is_empty() {
+            self.content_string.push_str("\n");
+            return;
+        }
+        // 3 unsigned chars and 2 unsigned ints per entity
+        self.write_array_start("ldtk_entity", format!("{}_entities", array_name_prefix).as_str(), entities.len() * 7);
+        for entity in entities.iter() {
+            self.content_string.push_str(
+                format!("\t{{ {}, {}, {}, {}, {} }}, // {}\n", entity.type_index, entity.tile_x, entity.tile_y, entity.pixel_x, entity.pixel_y, entity.name).as_str()
+            );
+        }
+        self.content_string.push_str("};\n\n");
+    }
+
+    /// Writes constants that describe the tile index ranges of the animations (tags) of an Aseprite tileset
+    pub fn write_tile_animation_ranges(&mut self, tile_animation_ranges: &Vec<AsepriteTileAnimationRange>) {
+        self.content_string.push_str("// Tile index ranges of the animations (tags) in the Aseprite tileset\n");
+        for tile_animation_range in tile_animation_ranges.iter() {
+            self.write_constant("size_t", format!("{}_animation_first_tile_index", tile_animation_range.name).as_str(), tile_animation_range.first_tile_index);
+            self.write_constant("size_t", format!("{}_animation_tiles_per_frame", tile_animation_range.name).as_str(), tile_animation_range.tiles_per_frame);
+            self.write_constant("size_t", format!("{}_animation_frame_count", tile_animation_range.name).as_str(), tile_animation_range.frame_count);
+        }
+        self.content_string.push_str("\n");
+    }
+
+    /// Writes a constant for the bit of every tile property and the property byte of every tile, indexed by tile index
+    pub fn write_tile_properties(&mut self, tiledata_vec: &Vec<TileInfo>, use_hex_notation: bool) {
+        let tile_property_bits = tile_property_bits(tiledata_vec);
+        let array_name = format!("{}_tile_properties", self.filename_without_extension_string());
+
+        self.content_string.push_str("// Bits of the tile properties\n");
+        for (property, bit) in tile_property_bits.iter() {
+            self.write_constant("unsigned char", format!("{}_{}", array_name, c_identifier_from_str(property)).as_str(), format!("{:#04x}", bit));
+        }
+        self.content_string.push_str("\n");
+
+        self.write_byte_array(&array_name, &tile_property_bytes(tiledata_vec, &tile_property_bits), use_hex_notation);
+    }
+
+    pub fn write_tileset(&mut self, tiledata_vec: &Vec<TileInfo>, use_hex_notation: bool, compression_format: CompressionFormat, tile_data_format: TileDataFormat) {
+        self.write_tile_name_constants(tiledata_vec);
+
+        let array_name = format!("{}_tile_data", self.filename_without_extension_string());
+        self.write_tile_data_format_constants(&array_name, tile_data_format);
+        self.write_tile_data(&array_name, tiledata_vec, 0, use_hex_notation, compression_format, tile_data_format);
+    }
+
+    /// Writes the tileset, but with the tile data split into parts that are placed in different ROM banks. Every part
+    /// is a separate output file, that contains the tiles of the part (bank, tile count) and is returned.
+    pub fn write_split_tileset(&mut self, tiledata_vec: &Vec<TileInfo>, tile_data_parts: &Vec<(u32, usize)>, use_hex_notation: bool, tile_data_format: TileDataFormat) -> Vec<Output_info_for_a_single_file> {
+        self.write_tile_name_constants(tiledata_vec);
+
+        let array_name = format!("{}_tile_data", self.filename_without_extension_string());
+        self.write_tile_data_format_constants(&array_name, tile_data_format);
+        self.write_constant("size_t", format!("{}_tile_count", array_name).as_str(), tiledata_vec.len());
+        self.write_constant("size_t", format!("{}_part_count", array_name).as_str(), tile_data_parts.len());
+        self.content_string.push_str("\n");
+
+        let mut res = Vec::new();
+        let mut first_tile_index = 0;
+        for (part_index, (bank, tile_count)) in tile_data_parts.iter().enumerate() {
+            let part_file_name = format!("{}_part{}", self.filename_without_extension_string(), part_index);
+            let mut part_output_info = Output_info_for_a_single_file::new(self.specified_path.with_file_name(part_file_name.as_str()));
+            part_output_info.set_rom_bank(RomBank::Fixed(*bank));
+
+            let part_array_name = format!("{}_part{}", array_name, part_index);
+            part_output_info.write_constant("size_t", format!("{}_first_tile_index", part_array_name).as_str(), first_tile_index);
+            part_output_info.write_tile_data(&part_array_name, &tiledata_vec[first_tile_index..(first_tile_index + tile_count)], first_tile_index, use_hex_notation, CompressionFormat::None, tile_data_format);
+
+            first_tile_index += tile_count;
+            res.push(part_output_info);
+        }
+
+        return res;
+    }
+
+    /// Writes the character to tile index mapping of a font: a lookup table for the 256 Latin-1 characters (which
+    /// include umlauts), that maps characters without a glyph to fallback_tile_index, and the Unicode code points of
+    /// all characters together with their tile indices, sorted by code point.
+    pub fn write_font_character_tables(&mut self, character_tile_indices: &Vec<(char, u8)>, fallback_tile_index: u8, use_hex_notation: bool) {
+        let array_name_prefix = format!("{}_font", self.filename_without_extension_string());
+
+        let mut latin1_tile_indices = vec![fallback_tile_index; 256];
+        for (character, tile_index) in character_tile_indices.iter().filter(|(x, _)| (*x as u32) < 256) {
+            latin1_tile_indices[*character as usize] = *tile_index;
+        }
+        self.content_string.push_str(format!("// Tile indices of the Latin-1 characters, characters without a glyph use the tile {}\n", fallback_tile_index).as_str());
+        self.write_byte_array(format!("{}_latin1_to_tile_index", array_name_prefix).as_str(), &latin1_tile_indices, use_hex_notation);
+
+        self.content_string.push_str("// Unicode code points of all characters of the font and their tile indices\n");
+        self.write_constant("size_t", format!("{}_character_count", array_name_prefix).as_str(), character_tile_indices.len());
+        self.write_array_start("unsigned long", format!("{}_characters", array_name_prefix).as_str(), 4 * character_tile_indices.len());
+        for (character, _) in character_tile_indices.iter() {
+            self.content_string.push_str(format!("\t{:#06x}, // '{}'\n", *character as u32, character).as_str());
+        }
+        self.content_string.push_str("};\n\n");
+        self.write_byte_array(
+            format!("{}_character_tile_indices", array_name_prefix).as_str(),
+            &character_tile_indices.iter().map(|(_, tile_index)| *tile_index).collect::<Vec<u8>>(),
+            use_hex_notation
+        );
+    }
+
+    /// Writes the SGB packets, that set the palettes and assign them to the tiles of the screen
+    pub fn write_sgb_packets(&mut self, sgb_colorization: &SgbColorization, use_hex_notation: bool) {
+        let array_name_prefix = self.filename_without_extension_string();
+        let (attribute_packets, use_attr_blk) = sgb_attribute_packets(sgb_colorization);
+
+        self.content_string.push_str(format!("// SGB packets (16 bytes each) for {} palette(s), PAL01 is followed by PAL23 if more than two palettes are used\n", sgb_colorization.palettes.len()).as_str());
+        self.write_byte_array(format!("{}_sgb_palette_packets", array_name_prefix).as_str(), &sgb_palette_packets(sgb_colorization), use_hex_notation);
+        self.content_string.push_str(format!("// {} packets, that assign the palettes to the tiles of the screen\n", if use_attr_blk { "ATTR_BLK" } else { "ATTR_CHR" }).as_str());
+        self.write_byte_array(format!("{}_sgb_attribute_packets", array_name_prefix).as_str(), &attribute_packets, use_hex_notation);
+    }
+
+    /// Writes the values of the DMG palette registers, that display the palette indices with the given shades
+    pub fn write_dmg_palette_registers(&mut self, dmg_shades: &[u8; 4]) {
+        let array_name_prefix = self.filename_without_extension_string();
+        let register_value = dmg_palette_register_value(dmg_shades);
         self.content_string.push_str(
             format!(
-                "const unsigned char {}_tileindex__array",
-                self.filename_without_extension_string()
-            )
-            .as_str(),
+                "// DMG palette register value, the palette indices 0-3 are displayed with the shades {}, {}, {} and {} (0 = white, 3 = black).\n\
+                // Only BGP is generated, its value can be written to OBP0 and OBP1 as well, since they ignore the shade of the\n\
+                // (transparent) palette index 0 of sprites.\n",
+                dmg_shades[0], dmg_shades[1], dmg_shades[2], dmg_shades[3]
+            ).as_str()
         );
-        self.write_tilemap_byte_array(&index_array.0, use_hex_notation);
+        self.write_constant("unsigned char", format!("{}_dmg_bgp", array_name_prefix).as_str(), format!("{:#04x}", register_value));
+        self.content_string.push_str("\n");
+    }
 
+    /// Writes the pixel widths of the glyphs of a variable width font, indexed by tile index
+    pub fn write_glyph_widths(&mut self, glyph_widths: &Vec<u8>, use_hex_notation: bool) {
+        self.content_string.push_str("// Pixel widths of the glyphs of the tiles for variable width font rendering\n");
+        self.write_byte_array(format!("{}_font_glyph_widths", self.filename_without_extension_string()).as_str(), glyph_widths, use_hex_notation);
     }
 
-    pub fn write_tileset(&mut self, tiledata_vec: &Vec<TileInfo>, use_hex_notation: bool) {
+    /// Writes the control codes of encoded texts. Since every text file contains them, they are guarded.
+    pub fn write_text_control_codes(&mut self) {
+        let definitions = format!(
+            "#ifndef ITGBA_TEXT_CONTROL_CODES\n\
+            #define ITGBA_TEXT_CONTROL_CODES\n\
+            #define TEXT_PAUSE {:#04x}\n\
+            #define TEXT_NEWLINE {:#04x}\n\
+            #define TEXT_END {:#04x}\n\
+            #endif\n\n",
+            TEXT_CONTROL_CODE_PAUSE, TEXT_CONTROL_CODE_NEWLINE, TEXT_CONTROL_CODE_END
+        );
+        self.content_string.push_str(definitions.as_str());
+        self.header_declarations_string.push_str(definitions.as_str());
+    }
 
+    /// Writes the colors of 1bpp tile data, that are passed to GBDK's set_1bpp_colors
+    fn write_tile_data_format_constants(&mut self, array_name: &str, tile_data_format: TileDataFormat) {
+        if let TileDataFormat::Bpp1 { background_palette_index, foreground_palette_index } = tile_data_format {
+            self.content_string.push_str("// 1bpp tile data, load it with set_1bpp_colors(foreground, background) and set_bkg_1bpp_data\n");
+            self.write_constant("unsigned char", format!("{}_1bpp_foreground_color", array_name).as_str(), foreground_palette_index);
+            self.write_constant("unsigned char", format!("{}_1bpp_background_color", array_name).as_str(), background_palette_index);
+            self.content_string.push_str("\n");
+        }
+    }
 
+    fn write_tile_name_constants(&mut self, tiledata_vec: &Vec<TileInfo>) {
         // Write constants that give names to the indices of tiles that have an identifier name
         self.content_string.push_str(
             format!(
                 "// Constants for easier tile indexing. These constants are generated using\n\
                 // the file names of the individual tiles if they don't have an ignore prefix \"{}\"\n\
-                // and are valid c identifiers.", crate::read_input::IGNORE_PREFIX).as_str()
+                // and are valid c identifiers.\n", crate::read_input::IGNORE_PREFIX).as_str()
         );
         for (tile_index, tile_info) in tiledata_vec.iter().enumerate() {
            if let Some(name) = tile_info.name.as_ref() {
-                self.content_string.push_str(
-                    format!("const size_t {}_tile_index = {};\n", name, tile_index).as_str()
-                );
+                self.write_constant("size_t", format!("{}_tile_index", name).as_str(), tile_index);
            }
         }
         self.content_string.push_str("\n");
+    }
 
-        self.content_string.push_str(
-            format!("const unsigned char {}[] = \n", self.c_file_name_string().as_str()).as_str()
-        );
+    /// Writes the tile data of tiles, whose first tile has the index first_tile_index in the tileset
+    fn write_tile_data(&mut self, array_name: &str, tiles: &[TileInfo], first_tile_index: usize, use_hex_notation: bool, compression_format: CompressionFormat, tile_data_format: TileDataFormat) {
+        let tile_data = tile_data_bytes(tiles, tile_data_format);
 
-        for (tile_index, tile_info) in tiledata_vec.iter().enumerate() {
+        let rom_data_size_before_tile_data = self.rom_data_size;
+        self.report.tile_count += tiles.len();
+        self.report.used_tile_indices.extend(first_tile_index..(first_tile_index + tiles.len()));
+        self.write_constant("size_t", format!("{}_tile_count", array_name).as_str(), tiles.len());
+
+        // Compressed data can't be split into tiles and lines anymore
+        if compression_format != CompressionFormat::None {
+            let compressed_tile_data = compress(&tile_data, compression_format);
+            self.write_compressed_size_constants(array_name, compression_format, tile_data.len(), compressed_tile_data.len());
+            self.write_compressed_byte_array(array_name, &compressed_tile_data, tile_data.len(), use_hex_notation);
+            self.report.tile_data_size += self.rom_data_size - rom_data_size_before_tile_data;
+            return;
+        }
+
+        self.write_array_start("unsigned char", array_name, tile_data.len());
+
+        // 2bpp tiles store a line in 2 bytes, 1bpp tiles in a single byte. Every row of the output contains 4 bytes.
+        let bytes_per_line = tile_data_format.bytes_per_tile() / 8;
+        let lines_per_row = 4 / bytes_per_line;
+
+        for (tile_index, tile_bytes) in tile_data.chunks(tile_data_format.bytes_per_tile()).enumerate() {
             
-            self.content_string.push_str(format!("\n\t// Tile {}\n", tile_index).as_str());
+            self.content_string.push_str(format!("\n\t// Tile {}\n", first_tile_index + tile_index).as_str());
 
             for y in 0..8 {
-                if (y  % 2 == 0) {
+                if (y % lines_per_row == 0) {
                     self.content_string.push_str("\t");
                 }
-                // write 2 bytes corresponding to the line x
-                let mut first_byte: u8 = 0; // stores the least significant bits of the palette indices
-                let mut second_byte: u8 = 0; // stores the highest significant bits " 
-
-                for x in 0..8 {
-                    let palette_index = tile_info.color_array.get(x,y);
-                    first_byte += ((palette_index & 1u8) << 7) >> x;
-                    second_byte += (((palette_index & 2u8) >> 1) << 7) >> x;
+                // write the bytes corresponding to the line y. For 2bpp tiles the first byte stores the least
+                // significant bits of the palette indices and the second byte the most significant bits
+                for byte in tile_bytes[(bytes_per_line * y)..(bytes_per_line * (y + 1))].iter() {
+                    self.content_string
+                        .push_str(match use_hex_notation {
+                            true => format!("{:#04x}, ", byte),
+                            false => format!("{:#010b}, ", byte),
+                        }.as_str());
                 }
 
-                self.content_string
-                    .push_str(match use_hex_notation {
-                        true => format!("{:#04x}, {:#04x}, ", first_byte, second_byte),
-                        false => format!("{:#010b}, {:#010b}, ", first_byte, second_byte),
-            }.as_str());
-
-                if ((y + 1) % 2 == 0) {
-                    self.content_string.push_str(format!(" // Line {}-{}\n", y-1, y).as_str());
+                if ((y + 1) % lines_per_row == 0) {
+                    self.content_string.push_str(format!(" // Line {}-{}\n", y + 1 - lines_per_row, y).as_str());
                 }
             }
         }
+        self.content_string.push_str("};\n\n");
+        self.report.tile_data_size += self.rom_data_size - rom_data_size_before_tile_data;
 
+    }
 
+    /// Writes the sizes of compressed data, so that loaders know how much memory to allocate
+    pub fn write_compressed_size_constants(&mut self, array_name: &str, compression_format: CompressionFormat, uncompressed_size: usize, compressed_size: usize) {
+        self.content_string.push_str(format!("// {:?} compressed\n", compression_format).as_str());
+        self.write_constant("size_t", format!("{}_uncompressed_size", array_name).as_str(), uncompressed_size);
+        self.write_constant("size_t", format!("{}_compressed_size", array_name).as_str(), compressed_size);
     }
-    pub fn write_tilemap_byte_array(&mut self, byte_array: &TilemapByteArray, use_hex_notation: bool) {
 
-        self.content_string.push_str(
-            format!("const unsigned char {}[] = \n", self.c_file_name_string().as_str()).as_str()
-        );
+    /// Writes an arbitrary byte array, that is compressed as a whole (together with its size constants) unless
+    /// the compression format is CompressionFormat::None
+    pub fn write_maybe_compressed_byte_array(&mut self, array_name: &str, bytes: &[u8], use_hex_notation: bool, compression_format: CompressionFormat) {
+        if compression_format == CompressionFormat::None {
+            self.write_byte_array(array_name, bytes, use_hex_notation);
+            return;
+        }
+        let compressed_bytes = compress(bytes, compression_format);
+        self.write_compressed_size_constants(array_name, compression_format, bytes.len(), compressed_bytes.len());
+        self.write_compressed_byte_array(array_name, &compressed_bytes, bytes.len(), use_hex_notation);
+    }
+
+    /// Writes compressed data as a byte array, which counts with its uncompressed size towards the uncompressed
+    /// ROM data size
+    pub fn write_compressed_byte_array(&mut self, array_name: &str, compressed_bytes: &[u8], uncompressed_size: usize, use_hex_notation: bool) {
+        self.write_byte_array(array_name, compressed_bytes, use_hex_notation);
+        // write_byte_array counted the compressed size, which is replaced by the uncompressed size (the compressed
+        // data can be larger than the uncompressed data)
+        self.uncompressed_rom_data_size -= compressed_bytes.len();
+        self.uncompressed_rom_data_size += uncompressed_size;
+    }
+
+    /// Writes an arbitrary byte array with 8 bytes per line
+    pub fn write_byte_array(&mut self, array_name: &str, bytes: &[u8], use_hex_notation: bool) {
+        self.write_array_start("unsigned char", array_name, bytes.len());
+
+        for (byte_index, byte) in bytes.iter().enumerate() {
+            self.content_string
+                .push_str(match use_hex_notation {
+                    true => format!("{:#04x}, ", byte),
+                    false => format!("{:#010b}, ", byte),
+                }.as_str());
+
+            if ((byte_index + 1) % 8 == 0) {
+                self.content_string.push_str("\n");
+            }
+        }
+        if bytes.len() % 8 != 0 {
+            self.content_string.push_str("\n");
+        }
+        self.content_string.push_str("};\n\n");
+    }
+
+    pub fn write_tilemap_byte_array(&mut self, array_name: &str, byte_array: &TilemapByteArray, use_hex_notation: bool, map_compression: MapCompression) {
+
+        if map_compression.compression_format != CompressionFormat::None {
+            self.write_compressed_tilemap_byte_array(array_name, byte_array, use_hex_notation, map_compression);
+            return;
+        }
+
+        self.write_array_start("unsigned char", array_name, 32 * 32);
 
         let mut byte_index = 0;
         for y in 0..32{
@@ -220,8 +1105,58 @@ impl Output_info_for_a_single_file {
                 byte_index+=1;
             }
         }
+        self.content_string.push_str("};\n\n");
+
+    }
+
+    /// Writes a compressed map byte array. If the map is compressed in chunks (rows or columns), every chunk
+    /// is compressed separately and an additional array contains the offset of every chunk in the compressed data.
+    pub fn write_compressed_tilemap_byte_array(&mut self, array_name: &str, byte_array: &TilemapByteArray, use_hex_notation: bool, map_compression: MapCompression) {
+        let (compressed_bytes, chunk_offsets) = compressed_map_chunks(byte_array, map_compression);
+
+        let uncompressed_size = 32 * 32;
+        self.write_compressed_size_constants(array_name, map_compression.compression_format, uncompressed_size, compressed_bytes.len());
+
+        if map_compression.chunking != MapCompressionChunking::Whole {
+            self.content_string.push_str(
+                format!(
+                    "// Offsets of the separately compressed {} in {}\n",
+                    match map_compression.chunking { MapCompressionChunking::Rows => "rows", _ => "columns" },
+                    array_name
+                ).as_str()
+            );
+            self.write_array_start("unsigned int", format!("{}_chunk_offsets", array_name).as_str(), 2 * chunk_offsets.len());
+            self.content_string.push_str(
+                format!("{}\n}};\n", chunk_offsets.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ")).as_str()
+            );
+        }
 
+        self.write_compressed_byte_array(array_name, &compressed_bytes, uncompressed_size, use_hex_notation);
     }
 
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incompressible_data_counts_with_its_uncompressed_size() {
+        let mut state: u32 = 12345;
+        let noise: Vec<u8> = (0..64).map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect();
+
+        for compression_format in [CompressionFormat::Gbcompress, CompressionFormat::Pb16, CompressionFormat::Rle] {
+            let compressed_size = compress(&noise, compression_format).len();
+            assert!(compressed_size > noise.len(), "{:?} compresses the noise", compression_format);
+
+            let mut output_info = Output_info_for_a_single_file::new("noise.png");
+            output_info.write_maybe_compressed_byte_array("noise", &noise, false, compression_format);
+            assert_eq!(output_info.rom_data_size, compressed_size);
+            assert_eq!(output_info.uncompressed_rom_data_size, noise.len());
+        }
+    }
+}
@@ -0,0 +1,50 @@
+// This module finds the tiles of the reference tileset that aren't used by any map and removes them from the
+// tileset if pruning is enabled. The tile indices of the maps are remapped to the indices of the pruned tileset.
+
+use std::collections::BTreeSet;
+
+use crate::input_data_representation_types::*;
+
+/// Prints the indices (and names) of the tiles that aren't contained in used_tile_indices
+pub fn print_unused_tiles(tile_info_vec: &Vec<TileInfo>, used_tile_indices: &BTreeSet<usize>) {
+    let unused_tiles: Vec<String> = tile_info_vec.iter().enumerate()
+        .filter(|(tile_index, _)| !used_tile_indices.contains(tile_index))
+        .map(|(tile_index, tile_info)| match tile_info.name.as_ref() {
+            Some(name) => format!("{} ({})", tile_index, name),
+            None => tile_index.to_string(),
+        })
+        .collect();
+
+    if unused_tiles.is_empty() {
+        return;
+    }
+    println!("{} of {} tiles aren't used by any map: {}", unused_tiles.len(), tile_info_vec.len(), unused_tiles.join(", "));
+}
+
+/// Returns the new tile index of every tile after the unused tiles were removed. Unused tiles receive
+/// the index 0, since maps don't refer to them.
+pub fn tile_index_remap_from_used_tile_indices(tile_count: usize, used_tile_indices: &BTreeSet<usize>) -> Vec<u8> {
+    let mut res = vec![0; tile_count];
+    for (new_tile_index, old_tile_index) in used_tile_indices.iter().enumerate() {
+        res[*old_tile_index] = new_tile_index as u8;
+    }
+    return res;
+}
+
+/// Removes the tiles that aren't contained in used_tile_indices
+pub fn pruned_tile_info_vec(tile_info_vec: Vec<TileInfo>, used_tile_indices: &BTreeSet<usize>) -> Vec<TileInfo> {
+    tile_info_vec.into_iter().enumerate()
+        .filter(|(tile_index, _)| used_tile_indices.contains(tile_index))
+        .map(|(_, tile_info)| tile_info)
+        .collect()
+}
+
+pub fn remapped_tile_index_array(index_array: &TileIndexArray, tile_index_remap: &Vec<u8>) -> TileIndexArray {
+    let mut res: TileIndexArray = unsafe { std::mem::zeroed() };
+    for x in 0..32 {
+        for y in 0..32 {
+            res.assign(x, y, tile_index_remap[index_array.get(x, y) as usize]);
+        }
+    }
+    return res;
+}
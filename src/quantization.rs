@@ -0,0 +1,133 @@
+// This module maps the colors of true-color images (e.g. painted or photographic art) to the colors of the
+// palette, optionally with dithering. Pixels that already have a palette color are never altered, so images
+// that only use palette colors are read exactly as without quantization.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use image::Rgb;
+
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+use crate::read_input::*;
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorDistanceMetric {
+    /// Euclidean distance of the RGB values
+    #[value(name = "euclidean")]
+    Euclidean,
+    /// Euclidean distance weighted by the mean red value, which approximates the perceived difference better
+    #[value(name = "redmean")]
+    Redmean,
+    /// Difference of the luminances, suited for grayscale palettes like the DMG shades
+    #[value(name = "luminance")]
+    Luminance,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Dithering {
+    /// Every pixel receives the nearest palette color
+    #[value(name = "none")]
+    None,
+    /// A 4x4 Bayer matrix, which is aligned with the tiles, so equal tiles are dithered equally
+    #[value(name = "ordered")]
+    Ordered,
+    /// The quantization error of every pixel is diffused to its neighbors (also across tile borders)
+    #[value(name = "floyd_steinberg")]
+    FloydSteinberg,
+}
+
+pub struct QuantizationSettings {
+    pub color_distance_metric: ColorDistanceMetric,
+    pub dithering: Dithering,
+}
+
+// Thresholds of ordered dithering, divided by 16
+const BAYER_MATRIX: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+// Ordered dithering offsets the colors by up to half the distance of two neighboring DMG shades
+const ORDERED_DITHERING_SPREAD: f32 = 255.0 / 3.0;
+
+/// Quantizes the colors of an RGB image to the palette colors. Images with palette indices are returned unchanged.
+pub fn quantized_tile_source_image(tile_source_image: TileSourceImage, color_palette: &ColorPalette, quantization_settings: Option<&QuantizationSettings>, path: &Path) -> TileSourceImage {
+    match (tile_source_image, quantization_settings) {
+        (TileSourceImage::Rgb(image), Some(quantization_settings)) => TileSourceImage::Rgb(quantized_image(&image, color_palette, quantization_settings, path)),
+        (tile_source_image, _) => tile_source_image,
+    }
+}
+
+/// Replaces every pixel that doesn't have a palette color with a palette color and prints how many pixels were altered
+pub fn quantized_image(image: &image::RgbImage, color_palette: &ColorPalette, quantization_settings: &QuantizationSettings, path: &Path) -> image::RgbImage {
+    let mut res = image.clone();
+    // Colors including the diffused errors of Floyd-Steinberg dithering
+    let mut colors: Vec<[f32; 3]> = image.pixels().map(|x| [x[0] as f32, x[1] as f32, x[2] as f32]).collect();
+    let mut altered_pixel_count = 0;
+
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let pixel = *image.get_pixel(x, y);
+            if color_palette.contains(&pixel) {
+                continue;
+            }
+
+            let mut color = colors[(y * image.width() + x) as usize];
+            if quantization_settings.dithering == Dithering::Ordered {
+                let threshold = (BAYER_MATRIX[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0 - 0.5;
+                color = color.map(|channel| channel + threshold * ORDERED_DITHERING_SPREAD);
+            }
+            let color = color.map(|channel| channel.clamp(0.0, 255.0));
+
+            let palette_color = *color_palette.iter()
+                .min_by(|a, b| color_distance(&color, a, quantization_settings.color_distance_metric).total_cmp(&color_distance(&color, b, quantization_settings.color_distance_metric)))
+                .unwrap();
+            res.put_pixel(x, y, palette_color);
+            if palette_color != pixel {
+                altered_pixel_count += 1;
+            }
+
+            if quantization_settings.dithering == Dithering::FloydSteinberg {
+                let error: [f32; 3] = std::array::from_fn(|i| color[i] - palette_color[i] as f32);
+                for (offset_x, offset_y, weight) in [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)] {
+                    let (neighbor_x, neighbor_y) = (x as i64 + offset_x, y as i64 + offset_y);
+                    if neighbor_x < 0 || neighbor_x >= image.width() as i64 || neighbor_y >= image.height() as i64 {
+                        continue;
+                    }
+                    let neighbor_color = &mut colors[(neighbor_y * image.width() as i64 + neighbor_x) as usize];
+                    for i in 0..3 {
+                        neighbor_color[i] += error[i] * weight / 16.0;
+                    }
+                }
+            }
+        }
+    }
+
+    if altered_pixel_count > 0 {
+        print_warning(format!(
+            "{} of the {} pixels of \"{}\" don't have a palette color and were quantized",
+            altered_pixel_count, image.width() * image.height(), path.imm_to_str()
+        ).as_str());
+    }
+
+    return res;
+}
+
+fn color_distance(color: &[f32; 3], palette_color: &Rgb<u8>, color_distance_metric: ColorDistanceMetric) -> f32 {
+    let [r, g, b] = *color;
+    let (palette_r, palette_g, palette_b) = (palette_color[0] as f32, palette_color[1] as f32, palette_color[2] as f32);
+    match color_distance_metric {
+        ColorDistanceMetric::Euclidean => (r - palette_r).powi(2) + (g - palette_g).powi(2) + (b - palette_b).powi(2),
+        ColorDistanceMetric::Redmean => {
+            let red_mean = (r + palette_r) / 2.0;
+            (2.0 + red_mean / 256.0) * (r - palette_r).powi(2) + 4.0 * (g - palette_g).powi(2) + (2.0 + (255.0 - red_mean) / 256.0) * (b - palette_b).powi(2)
+        },
+        ColorDistanceMetric::Luminance => {
+            let luminance = |r: f32, g: f32, b: f32| 0.299 * r + 0.587 * g + 0.114 * b;
+            (luminance(r, g, b) - luminance(palette_r, palette_g, palette_b)).abs()
+        },
+    }
+}
@@ -0,0 +1,514 @@
+// This module reads Aseprite files (.aseprite/.ase) natively, so that pixel artists don't have to export
+// PNG files first. The layers of every frame are merged into a single image (the selection of layers can be
+// configured) and all frames are placed next to each other from left-to-right. Since tilesets are read column by
+// column, the tiles of the first frame receive the first tile indices, followed by the tiles of the second frame
+// and so on. Slices are used to name tiles and tags are used to describe animation ranges of tiles.
+//
+// File format specification: https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use image::{Rgb, Rgba};
+
+use crate::helper::*;
+
+const ASEPRITE_HEADER_MAGIC_NUMBER: u16 = 0xA5E0;
+const ASEPRITE_FRAME_MAGIC_NUMBER: u16 = 0xF1FA;
+
+const ASEPRITE_OLD_PALETTE_CHUNK: u16 = 0x0004;
+const ASEPRITE_LAYER_CHUNK: u16 = 0x2004;
+const ASEPRITE_CEL_CHUNK: u16 = 0x2005;
+const ASEPRITE_TAGS_CHUNK: u16 = 0x2018;
+const ASEPRITE_PALETTE_CHUNK: u16 = 0x2019;
+const ASEPRITE_SLICE_CHUNK: u16 = 0x2022;
+
+/// Decides which layers of Aseprite files are merged into the image used by ITGBA
+pub enum AsepriteLayerSelection {
+    /// Merge every visible layer
+    MergeVisible,
+    /// Merge only the layers with the given names (or the layers inside of groups with the given names),
+    /// regardless of their visibility
+    Choose(Vec<String>),
+    /// Merge every visible layer, except for the layers with the given names
+    Skip(Vec<String>),
+}
+
+pub fn is_aseprite_path(path: &Path) -> bool {
+    matches!(path.extension().and_then(|x| x.to_str()), Some("aseprite") | Some("ase"))
+}
+
+/// A tag of an Aseprite file, which marks an animation as a range of frames
+pub struct AsepriteTag {
+    pub name: String,
+    pub from_frame: u32,
+    pub to_frame: u32,
+}
+
+/// A slice of an Aseprite file, which gives a name to a rectangle of the image (in the first frame)
+pub struct AsepriteSlice {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+struct AsepriteLayer {
+    name: String,
+    visible: bool,
+    is_group: bool,
+    child_level: u16,
+    blend_mode: u16,
+    opacity: u8,
+}
+
+struct AsepriteCel {
+    layer_index: usize,
+    x: i32,
+    y: i32,
+    opacity: u8,
+    width: u32,
+    height: u32,
+    // Raw pixels in the color depth of the file
+    pixels: Vec<u8>,
+}
+
+pub struct AsepriteFile {
+    pub width: u32,
+    pub height: u32,
+    // 32: RGBA, 16: grayscale, 8: indexed
+    color_depth: u16,
+    layer_opacity_is_valid: bool,
+    transparent_index: u8,
+    palette: Vec<Rgba<u8>>,
+    layers: Vec<AsepriteLayer>,
+    // cels of every frame
+    frames: Vec<Vec<AsepriteCel>>,
+    pub tags: Vec<AsepriteTag>,
+    pub slices: Vec<AsepriteSlice>,
+}
+
+impl AsepriteFile {
+    pub fn frame_count(&self) -> u32 {
+        self.frames.len() as u32
+    }
+
+    fn pixel_to_rgba(&self, pixels: &[u8], pixel_index: usize) -> Rgba<u8> {
+        match self.color_depth {
+            32 => Rgba([pixels[pixel_index * 4], pixels[pixel_index * 4 + 1], pixels[pixel_index * 4 + 2], pixels[pixel_index * 4 + 3]]),
+            16 => Rgba([pixels[pixel_index * 2], pixels[pixel_index * 2], pixels[pixel_index * 2], pixels[pixel_index * 2 + 1]]),
+            _ => {
+                let palette_index = pixels[pixel_index];
+                if palette_index == self.transparent_index {
+                    return Rgba([0, 0, 0, 0]);
+                }
+                *self.palette.get(palette_index as usize).unwrap_or(&Rgba([0, 0, 0, 255]))
+            }
+        }
+    }
+
+    // A layer is visible if it and all of its parent groups are visible
+    fn layer_is_selected(&self, layer_index: usize, layer_selection: &AsepriteLayerSelection) -> bool {
+        let mut names_of_layer_and_parents = vec![self.layers[layer_index].name.as_str()];
+        let mut visible = self.layers[layer_index].visible;
+        let mut child_level = self.layers[layer_index].child_level;
+        for parent_index in (0..layer_index).rev() {
+            if child_level == 0 {
+                break;
+            }
+            let parent = &self.layers[parent_index];
+            if parent.is_group && parent.child_level < child_level {
+                names_of_layer_and_parents.push(parent.name.as_str());
+                visible = visible && parent.visible;
+                child_level = parent.child_level;
+            }
+        }
+
+        match layer_selection {
+            AsepriteLayerSelection::MergeVisible => visible,
+            AsepriteLayerSelection::Choose(names) => names_of_layer_and_parents.iter().any(|x| names.iter().any(|name| name == x)),
+            AsepriteLayerSelection::Skip(names) => visible && (!names_of_layer_and_parents.iter().any(|x| names.iter().any(|name| name == x))),
+        }
+    }
+
+    /// Merges the selected layers of every frame and places the frames next to each other
+    pub fn merged_rgbaimage(&self, layer_selection: &AsepriteLayerSelection) -> image::RgbaImage {
+        // Transparent pixels of indexed files keep the color of the transparent palette entry, so that it
+        // can be used as a palette color
+        let background = match self.color_depth {
+            8 => {
+                let transparent_color = self.palette.get(self.transparent_index as usize).unwrap_or(&Rgba([0, 0, 0, 0]));
+                Rgba([transparent_color[0], transparent_color[1], transparent_color[2], 0])
+            },
+            _ => Rgba([0, 0, 0, 0]),
+        };
+        let mut res = image::RgbaImage::from_pixel(self.width * self.frame_count(), self.height, background);
+
+        for (frame_index, cels) in self.frames.iter().enumerate() {
+            let frame_offset_x = frame_index as i64 * self.width as i64;
+            // Cels are composited from the bottom layer to the top layer
+            let mut sorted_cels: Vec<&AsepriteCel> = cels.iter().collect();
+            sorted_cels.sort_by_key(|cel| cel.layer_index);
+
+            for cel in sorted_cels {
+                if !self.layer_is_selected(cel.layer_index, layer_selection) {
+                    continue;
+                }
+                let layer = &self.layers[cel.layer_index];
+                if layer.blend_mode != 0 {
+                    print_warning_once("Aseprite layers with other blend modes than \"normal\" are merged as if they used the normal blend mode");
+                }
+                let layer_opacity = if self.layer_opacity_is_valid { layer.opacity as u32 } else { 255 };
+
+                for cel_y in 0..cel.height {
+                    for cel_x in 0..cel.width {
+                        let x = cel.x as i64 + cel_x as i64;
+                        let y = cel.y as i64 + cel_y as i64;
+                        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+                            continue;
+                        }
+                        let source = self.pixel_to_rgba(&cel.pixels, (cel_y * cel.width + cel_x) as usize);
+                        let source_alpha = source[3] as u32 * cel.opacity as u32 / 255 * layer_opacity / 255;
+                        if source_alpha == 0 {
+                            continue;
+                        }
+                        let destination = res.get_pixel_mut((frame_offset_x + x) as u32, y as u32);
+                        *destination = blend_normal(*destination, source, source_alpha);
+                    }
+                }
+            }
+        }
+
+        return res;
+    }
+}
+
+// Alpha compositing of source over destination
+fn blend_normal(destination: Rgba<u8>, source: Rgba<u8>, source_alpha: u32) -> Rgba<u8> {
+    let destination_alpha = destination[3] as u32;
+    let result_alpha = source_alpha + destination_alpha * (255 - source_alpha) / 255;
+    if result_alpha == 0 {
+        return destination;
+    }
+    let mut res = Rgba([0, 0, 0, result_alpha as u8]);
+    for channel in 0..3 {
+        res[channel] = ((source[channel] as u32 * source_alpha + destination[channel] as u32 * destination_alpha * (255 - source_alpha) / 255) / result_alpha) as u8;
+    }
+    return res;
+}
+
+/// Reads the Aseprite file at path and merges its layers according to the layer selection
+pub fn rgbimage_from_aseprite_path(path: &Path, layer_selection: &AsepriteLayerSelection) -> image::RgbImage {
+    return rgbimage_from_aseprite_file(&aseprite_file_from_path(path), layer_selection);
+}
+
+/// Merges the layers of an Aseprite file according to the layer selection
+pub fn rgbimage_from_aseprite_file(aseprite_file: &AsepriteFile, layer_selection: &AsepriteLayerSelection) -> image::RgbImage {
+    let merged_image = aseprite_file.merged_rgbaimage(layer_selection);
+
+    // The alpha channel is dropped, like for every other image format
+    let mut res = image::RgbImage::new(merged_image.width(), merged_image.height());
+    for (x, y, pixel) in merged_image.enumerate_pixels() {
+        res.put_pixel(x, y, Rgb([pixel[0], pixel[1], pixel[2]]));
+    }
+    return res;
+}
+
+/// Returns the names that the slices of an Aseprite tileset give to the tiles at (tile_x, tile_y).
+/// Slices that cover a single tile name it after the slice, slices that cover multiple tiles name
+/// them after the slice followed by a running number (in tileset order, i.e. column by column).
+pub fn tile_names_from_aseprite_slices(aseprite_file: &AsepriteFile) -> HashMap<(u32, u32), String> {
+    let mut res = HashMap::new();
+    for slice in aseprite_file.slices.iter() {
+        if (slice.x < 0) || (slice.y < 0) || (slice.x % 8 != 0) || (slice.y % 8 != 0) || (slice.width % 8 != 0) || (slice.height % 8 != 0) || (slice.width == 0) || (slice.height == 0) {
+            print_warning(format!("The Aseprite slice \"{}\" isn't aligned to the 8x8 tile grid and won't be used to name tiles", slice.name).as_str());
+            continue;
+        }
+        let first_tile_x = slice.x as u32 / 8;
+        let first_tile_y = slice.y as u32 / 8;
+        let width_in_tiles = slice.width / 8;
+        let height_in_tiles = slice.height / 8;
+        let name = c_identifier_from_str(slice.name.as_str());
+
+        for tile_x in first_tile_x..(first_tile_x + width_in_tiles) {
+            for tile_y in first_tile_y..(first_tile_y + height_in_tiles) {
+                let tile_name = match width_in_tiles * height_in_tiles {
+                    1 => name.clone(),
+                    _ => format!("{}_{}", name, (tile_x - first_tile_x) * height_in_tiles + (tile_y - first_tile_y)),
+                };
+                res.insert((tile_x, tile_y), tile_name);
+            }
+        }
+    }
+    return res;
+}
+
+/// The range of tile indices that an Aseprite tag (animation) covers in a tileset
+pub struct AsepriteTileAnimationRange {
+    pub name: String,
+    pub first_tile_index: u32,
+    pub tiles_per_frame: u32,
+    pub frame_count: u32,
+}
+
+/// Translates the tags of an Aseprite tileset into ranges of tile indices. Every frame contains
+/// width_in_tiles*height_in_tiles tiles, except for the first frame that also contains the reference tile
+/// (if the tileset has one).
+pub fn tile_animation_ranges_from_aseprite_tags(aseprite_file: &AsepriteFile, has_reference_tile: bool) -> Vec<AsepriteTileAnimationRange> {
+    let tiles_per_frame = (aseprite_file.width / 8) * (aseprite_file.height / 8);
+    let first_tile_index_of_frame = |frame: u32| if frame == 0 || !has_reference_tile { frame * tiles_per_frame } else { frame * tiles_per_frame - 1 };
+
+    let mut res = Vec::new();
+    for tag in aseprite_file.tags.iter() {
+        if has_reference_tile && tag.from_frame == 0 && tag.to_frame > 0 {
+            print_warning(format!("The Aseprite tag \"{}\" contains the first frame, whose tiles are shifted by the reference tile, so its frames don't contain the same number of tiles", tag.name).as_str());
+        }
+        res.push(AsepriteTileAnimationRange {
+            name: c_identifier_from_str(tag.name.as_str()),
+            first_tile_index: first_tile_index_of_frame(tag.from_frame),
+            tiles_per_frame: tiles_per_frame,
+            frame_count: tag.to_frame - tag.from_frame + 1,
+        });
+    }
+    return res;
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, count: usize) -> &'a [u8] {
+        if self.position + count > self.bytes.len() {
+            panic!("Aseprite file ended unexpectedly");
+        }
+        let res = &self.bytes[self.position..(self.position + count)];
+        self.position += count;
+        return res;
+    }
+    fn skip(&mut self, count: usize) {
+        self.take(count);
+    }
+    fn byte(&mut self) -> u8 {
+        self.take(1)[0]
+    }
+    fn word(&mut self) -> u16 {
+        u16::from_le_bytes(self.take(2).try_into().unwrap())
+    }
+    fn short(&mut self) -> i16 {
+        i16::from_le_bytes(self.take(2).try_into().unwrap())
+    }
+    fn dword(&mut self) -> u32 {
+        u32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+    fn long(&mut self) -> i32 {
+        i32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+    fn string(&mut self) -> String {
+        let length = self.word() as usize;
+        String::from_utf8_lossy(self.take(length)).into_owned()
+    }
+}
+
+pub fn aseprite_file_from_path(path: &Path) -> AsepriteFile {
+    let bytes = std::fs::read(path).expect(format!("Failed to open the path \"{}\"", path.imm_to_str()).as_str());
+    let mut reader = ByteReader { bytes: bytes.as_slice(), position: 0 };
+
+    // Header
+    reader.skip(4);
+    if reader.word() != ASEPRITE_HEADER_MAGIC_NUMBER {
+        panic!("\"{}\" is not an Aseprite file", path.imm_to_str());
+    }
+    let frame_count = reader.word();
+    let width = reader.word() as u32;
+    let height = reader.word() as u32;
+    let color_depth = reader.word();
+    let flags = reader.dword();
+    reader.skip(2 + 4 + 4);
+    let transparent_index = reader.byte();
+    reader.skip(128 - 29);
+
+    let mut res = AsepriteFile {
+        width: width,
+        height: height,
+        color_depth: color_depth,
+        layer_opacity_is_valid: (flags & 1) != 0,
+        transparent_index: transparent_index,
+        palette: Vec::new(),
+        layers: Vec::new(),
+        frames: Vec::new(),
+        tags: Vec::new(),
+        slices: Vec::new(),
+    };
+
+    let bytes_per_pixel: usize = match color_depth {
+        32 => 4,
+        16 => 2,
+        8 => 1,
+        _ => panic!("Aseprite file \"{}\" uses the unsupported color depth {}", path.imm_to_str(), color_depth),
+    };
+
+    for _ in 0..frame_count {
+        let frame_start = reader.position;
+        let frame_size = reader.dword() as usize;
+        if reader.word() != ASEPRITE_FRAME_MAGIC_NUMBER {
+            panic!("Aseprite file \"{}\" contains a corrupt frame", path.imm_to_str());
+        }
+        let old_chunk_count = reader.word() as u32;
+        reader.skip(2 + 2);
+        let new_chunk_count = reader.dword();
+        let chunk_count = if new_chunk_count == 0 { old_chunk_count } else { new_chunk_count };
+
+        let mut cels = Vec::new();
+
+        for _ in 0..chunk_count {
+            let chunk_start = reader.position;
+            let chunk_size = reader.dword() as usize;
+            let chunk_type = reader.word();
+
+            match chunk_type {
+                ASEPRITE_OLD_PALETTE_CHUNK if res.palette.is_empty() => {
+                    let packet_count = reader.word();
+                    let mut palette_index = 0usize;
+                    for _ in 0..packet_count {
+                        palette_index += reader.byte() as usize;
+                        let color_count = match reader.byte() { 0 => 256, x => x as usize };
+                        for _ in 0..color_count {
+                            let color = reader.take(3);
+                            if res.palette.len() <= palette_index {
+                                res.palette.resize(palette_index + 1, Rgba([0, 0, 0, 255]));
+                            }
+                            res.palette[palette_index] = Rgba([color[0], color[1], color[2], 255]);
+                            palette_index += 1;
+                        }
+                    }
+                },
+                ASEPRITE_PALETTE_CHUNK => {
+                    let palette_size = reader.dword() as usize;
+                    let first_index = reader.dword() as usize;
+                    let last_index = reader.dword() as usize;
+                    reader.skip(8);
+                    res.palette.resize(palette_size.max(res.palette.len()), Rgba([0, 0, 0, 255]));
+                    for palette_index in first_index..=last_index {
+                        let entry_flags = reader.word();
+                        let color = reader.take(4);
+                        if palette_index < res.palette.len() {
+                            res.palette[palette_index] = Rgba([color[0], color[1], color[2], color[3]]);
+                        }
+                        if (entry_flags & 1) != 0 {
+                            reader.string();
+                        }
+                    }
+                },
+                ASEPRITE_LAYER_CHUNK => {
+                    let layer_flags = reader.word();
+                    let layer_type = reader.word();
+                    let child_level = reader.word();
+                    reader.skip(2 + 2);
+                    let blend_mode = reader.word();
+                    let opacity = reader.byte();
+                    reader.skip(3);
+                    let name = reader.string();
+                    res.layers.push(AsepriteLayer {
+                        name: name,
+                        visible: (layer_flags & 1) != 0,
+                        is_group: layer_type == 1,
+                        child_level: child_level,
+                        blend_mode: blend_mode,
+                        opacity: opacity,
+                    });
+                },
+                ASEPRITE_CEL_CHUNK => {
+                    let layer_index = reader.word() as usize;
+                    let x = reader.short() as i32;
+                    let y = reader.short() as i32;
+                    let opacity = reader.byte();
+                    let cel_type = reader.word();
+                    reader.skip(2 + 5);
+                    match cel_type {
+                        // raw image data
+                        0 => {
+                            let cel_width = reader.word() as u32;
+                            let cel_height = reader.word() as u32;
+                            let pixels = reader.take(cel_width as usize * cel_height as usize * bytes_per_pixel).to_vec();
+                            cels.push(AsepriteCel { layer_index, x, y, opacity, width: cel_width, height: cel_height, pixels });
+                        },
+                        // linked cel, that reuses the cel of the same layer in another frame
+                        1 => {
+                            let linked_frame_index = reader.word() as usize;
+                            let Some(linked_cel) = res.frames.get(linked_frame_index).and_then(|x| x.iter().find(|cel| cel.layer_index == layer_index)) else {
+                                panic!("Aseprite file \"{}\" contains a linked cel without a matching cel", path.imm_to_str());
+                            };
+                            cels.push(AsepriteCel {
+                                layer_index,
+                                x,
+                                y,
+                                opacity,
+                                width: linked_cel.width,
+                                height: linked_cel.height,
+                                pixels: linked_cel.pixels.clone(),
+                            });
+                        },
+                        // zlib compressed image data
+                        2 => {
+                            let cel_width = reader.word() as u32;
+                            let cel_height = reader.word() as u32;
+                            let compressed_pixels = reader.take(chunk_start + chunk_size - reader.position);
+                            let mut pixels = Vec::new();
+                            flate2::read::ZlibDecoder::new(compressed_pixels).read_to_end(&mut pixels)
+                                .expect(format!("Aseprite file \"{}\" contains corrupt compressed image data", path.imm_to_str()).as_str());
+                            pixels.resize(cel_width as usize * cel_height as usize * bytes_per_pixel, 0);
+                            cels.push(AsepriteCel { layer_index, x, y, opacity, width: cel_width, height: cel_height, pixels });
+                        },
+                        _ => print_warning_once("Aseprite tilemap layers aren't supported and will be ignored"),
+                    }
+                },
+                ASEPRITE_TAGS_CHUNK => {
+                    let tag_count = reader.word();
+                    reader.skip(8);
+                    for _ in 0..tag_count {
+                        let from_frame = reader.word() as u32;
+                        let to_frame = reader.word() as u32;
+                        reader.skip(1 + 2 + 6 + 3 + 1);
+                        let name = reader.string();
+                        res.tags.push(AsepriteTag { name, from_frame, to_frame });
+                    }
+                },
+                ASEPRITE_SLICE_CHUNK => {
+                    let key_count = reader.dword();
+                    let slice_flags = reader.dword();
+                    reader.skip(4);
+                    let name = reader.string();
+                    for key_index in 0..key_count {
+                        let key_frame = reader.dword();
+                        let x = reader.long();
+                        let y = reader.long();
+                        let slice_width = reader.dword();
+                        let slice_height = reader.dword();
+                        if (slice_flags & 1) != 0 {
+                            reader.skip(16);
+                        }
+                        if (slice_flags & 2) != 0 {
+                            reader.skip(8);
+                        }
+                        // Only the bounds of the first key (which starts at frame 0) are used
+                        if key_index == 0 && key_frame == 0 {
+                            res.slices.push(AsepriteSlice { name: name.clone(), x, y, width: slice_width, height: slice_height });
+                        }
+                    }
+                },
+                _ => {},
+            }
+
+            reader.position = chunk_start + chunk_size;
+        }
+
+        res.frames.push(cels);
+        reader.position = frame_start + frame_size;
+    }
+
+    return res;
+}
@@ -1,5 +1,5 @@
 use std::collections::{HashMap, LinkedList};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use clap::builder::styling::Color;
 use image::{DynamicImage, Rgb};
@@ -7,6 +7,9 @@ use derive_more::{Deref, DerefMut};
 
 use crate::helper::*;
 use crate::input_data_representation_types::*;
+use crate::read_aseprite::*;
+use crate::quantization::*;
+use crate::color_matching::*;
 
 const ALL_DIGITS_STR: &str = "0123456789";
 const CXX_VALID_IDENTIFIER_CHARACTERS: &str = "0123456789abcdefghijklmnopqrstuvwyz_ABCDEFGHIJKLMNOPQRSTUVWXYZ";
@@ -16,9 +19,18 @@ pub const IGNORE_PREFIX: &str = "__ignore";
 #[derive(Deref, DerefMut)]
 pub struct TileSearchmap(HashMap<TileColorArray, TileSearchmapValue>);
 
-pub fn read_tileset_info_from_path(path: &Path) -> (Vec<TileInfo>, ColorPalette) {
+/// Reads the tiles and the color palette of the reference tileset. Usually the first tile is the reference tile
+/// that contains the palette colors, but if use_palette_indices is set and the tileset is made of indexed images,
+/// the palette indices of the images are used directly and the tileset doesn't contain a reference tile.
+/// If infer_palette is set, the tileset doesn't contain a reference tile either and the palette is made of the
+/// colors used in the tileset (see inferred_color_palette). Colors that aren't part of the palette are matched
+/// to palette colors via the color matching settings, the remaining ones are quantized if quantization settings
+/// are supplied. If the tileset is an Aseprite file, its already parsed version is passed as aseprite_file.
+/// The returned bool tells whether the tileset contains a reference tile.
+pub fn read_tileset_info_from_path(path: &Path, aseprite_file: Option<&AsepriteFile>, use_palette_indices: bool, aseprite_layer_selection: &AsepriteLayerSelection, infer_palette: bool, palette_order: &Vec<Rgb<u8>>, color_matching_settings: Option<&ColorMatchingSettings>, quantization_settings: Option<&QuantizationSettings>) -> (Vec<TileInfo>, ColorPalette, bool) {
     let mut tile_info_vec = Vec::new();
     let mut color_palette: ColorPalette = unsafe { std::mem::uninitialized()};
+    let mut has_reference_tile = true;
 
     let mut read_palette_from_rgb_image = |image: image::RgbImage| {
         let mut pixels = image.pixels();
@@ -31,35 +43,53 @@ pub fn read_tileset_info_from_path(path: &Path) -> (Vec<TileInfo>, ColorPalette)
     };
 
     if path.is_dir() {
-        let mut directory_entry_vec = std::fs::read_dir(path)
-        .expect(format!("Couldn't read reference tileset directory members from path: {}", path.imm_to_str()).as_str())
-        .map(|x| x.unwrap())
-        .collect::<Vec<std::fs::DirEntry>>();
+        let directory_entry_vec = tile_image_paths_from_directory(path, &Vec::new());
 
-        directory_entry_vec.sort_by(|x, y| (x.file_name().to_str().unwrap()).cmp(
-            y.file_name().to_str().unwrap()
-        ));
-
-        if directory_entry_vec.len() < 2 {
+        if directory_entry_vec.is_empty() {
             panic!("Reference tileset directory should contain at least 2 members, a palette tile and a tileset tile");
         };
 
+        let indexed_first_tile = match use_palette_indices {
+            true => palette_index_image_from_path(&directory_entry_vec[0].0),
+            false => None,
+        };
+
         let mut directory_entry_iterator = directory_entry_vec.into_iter();
 
-        // first tile should contain the color palette
-        let first_tile_image = rgbimage_from_path(&directory_entry_iterator.next().unwrap().path());
+        match indexed_first_tile {
+            // indexed tiles supply their palette indices directly, so there is no reference tile
+            Some((_, indexed_palette)) => {
+                color_palette = color_palette_from_indexed_palette(&indexed_palette);
+                has_reference_tile = false;
+            },
+            None if infer_palette => {
+                let tile_images: Vec<image::RgbImage> = directory_entry_iterator.as_slice().iter().map(|(x, _)| rgbimage_from_path(x, aseprite_layer_selection)).collect();
+                color_palette = inferred_color_palette(&tile_images, palette_order, path);
+                has_reference_tile = false;
+            },
+            None => {
+                if directory_entry_iterator.len() < 2 {
+                    panic!("Reference tileset directory should contain at least 2 members, a palette tile and a tileset tile");
+                }
+
+                // first tile should contain the color palette
+                let first_tile_image = rgbimage_from_path(&directory_entry_iterator.next().unwrap().0, aseprite_layer_selection);
 
-        read_palette_from_rgb_image(first_tile_image);
+                read_palette_from_rgb_image(first_tile_image);
+            },
+        }
 
-        for dir_entry in  directory_entry_iterator {
-            let image = rgbimage_from_path(&dir_entry.path());
+        for (tile_image_path, tile_properties) in  directory_entry_iterator {
+            let image = tile_source_image_from_path(&tile_image_path, use_palette_indices, aseprite_layer_selection);
+            let image = palette_matched_tile_source_image(image, &color_palette, color_matching_settings, &tile_image_path);
+            let image = quantized_tile_source_image(image, &color_palette, quantization_settings, &tile_image_path);
 
             if (!(image.width() == 8)) || (!(image.height() == 8)) {
                 panic!("If the reference tileset is supplied via a directory path, all directory entries should be single tile 8x8 images. But\
-                the image: \"{}\" is not 8x8", dir_entry.path().imm_to_str())
+                the image: \"{}\" is not 8x8", tile_image_path.imm_to_str())
             }
 
-            let file_name_stem: std::ffi::OsString = dir_entry.path().file_stem().unwrap().to_os_string();
+            let file_name_stem: std::ffi::OsString = tile_image_path.file_stem().unwrap().to_os_string();
             
             let file_name_stem_str = file_name_stem.to_str().unwrap();
 
@@ -78,19 +108,40 @@ pub fn read_tileset_info_from_path(path: &Path) -> (Vec<TileInfo>, ColorPalette)
 
             tile_info_vec.push(
                 TileInfo {
-                    color_array: read_tile_from_image(0, 0, &image, &color_palette),
+                    color_array: read_tile_from_tile_source_image(0, 0, &image, &color_palette),
                     name: if (!file_name_has_ignore_prefix) && file_name_stem_is_valid_cxx_identifier {
                         Some(file_name_stem_str.to_string())
                     } else {
                         None
-                    }
+                    },
+                    properties: tile_properties
                 }
             )
 
         }
 
     } else {
-        let reference_tileset_image: image::RgbImage = rgbimage_from_path(path);
+        let reference_tileset_image: TileSourceImage = match aseprite_file {
+            Some(aseprite_file) => TileSourceImage::Rgb(rgbimage_from_aseprite_file(aseprite_file, aseprite_layer_selection)),
+            None => tile_source_image_from_path(path, use_palette_indices, aseprite_layer_selection),
+        };
+
+        match &reference_tileset_image {
+            TileSourceImage::Rgb(image) if infer_palette => {
+                color_palette = inferred_color_palette(&vec![image.clone()], palette_order, path);
+                has_reference_tile = false;
+            },
+            // the reference tile in the upper left corner contains the color palette
+            TileSourceImage::Rgb(image) => read_palette_from_rgb_image(image.clone()),
+            // indexed images supply their palette indices directly, so there is no reference tile
+            TileSourceImage::PaletteIndices(_) => {
+                color_palette = color_palette_from_indexed_palette(&palette_index_image_from_path(path).unwrap().1);
+                has_reference_tile = false;
+            },
+        }
+
+        let reference_tileset_image = palette_matched_tile_source_image(reference_tileset_image, &color_palette, color_matching_settings, path);
+        let reference_tileset_image = quantized_tile_source_image(reference_tileset_image, &color_palette, quantization_settings, path);
 
         // Check that the image has valid dimensions
         if (reference_tileset_image.width() % 8 > 0 || reference_tileset_image.height() % 8 > 0) {
@@ -101,30 +152,37 @@ pub fn read_tileset_info_from_path(path: &Path) -> (Vec<TileInfo>, ColorPalette)
         let image_height_in_tiles = reference_tileset_image.height() / 8;
 
         // Sanity check on tilemap size
-        if image_width_in_tiles * image_height_in_tiles -1 > u8::MAX as u32 {
+        if image_width_in_tiles * image_height_in_tiles - (has_reference_tile as u32) > u8::MAX as u32 + 1 {
             panic!("Reference tileset is too big. The map should contain only 256 data tiles apart from the reference tile at maximum.")
         }
 
-        if (image_width_in_tiles * image_height_in_tiles < 2) {
+        if has_reference_tile && (image_width_in_tiles * image_height_in_tiles < 2) {
             panic!("Image needs to contain at least 2 tiles: A reference tile for mapping colors to the pallete indices 0-3 and at least one data tile")
         }
 
 
 
 
+        // Slices of Aseprite files give names to the tiles they cover
+        let tile_names: HashMap<(u32, u32), String> = match aseprite_file {
+            Some(aseprite_file) => tile_names_from_aseprite_slices(aseprite_file),
+            None => HashMap::new(),
+        };
+
         // Iterate over the remaining tiles with starting at index (1,0),(2,0)... (image_width_in_tiles,0), (0,1) and so on...
         for tile_x in 0..image_width_in_tiles {
             for tile_y in 0..image_height_in_tiles {
                 // skip tile (0,0)
-                if (tile_x == 0 && tile_y == 0) {
+                if has_reference_tile && (tile_x == 0 && tile_y == 0) {
                     continue;
                 }
 
 
                 tile_info_vec.push(
                     TileInfo {
-                        color_array: read_tile_from_image(tile_x, tile_y, &reference_tileset_image, &color_palette),
-                        name: None
+                        color_array: read_tile_from_tile_source_image(tile_x, tile_y, &reference_tileset_image, &color_palette),
+                        name: tile_names.get(&(tile_x, tile_y)).cloned(),
+                        properties: Vec::new()
                     }
                 );
             }
@@ -132,14 +190,91 @@ pub fn read_tileset_info_from_path(path: &Path) -> (Vec<TileInfo>, ColorPalette)
 
     }
 
-    return (tile_info_vec, color_palette);
+    return (tile_info_vec, color_palette, has_reference_tile);
+}
+
+/// Infers the color palette from the distinct colors used in the images of a reference tileset without reference tile.
+/// The colors are sorted from the lightest to the darkest one (so that the palette indices match the DMG shades of the
+/// default palette register value 0xE4), unless palette_order specifies the order of the colors.
+pub fn inferred_color_palette(images: &Vec<image::RgbImage>, palette_order: &Vec<Rgb<u8>>, path: &Path) -> ColorPalette {
+    let mut colors: Vec<Rgb<u8>> = Vec::new();
+    for pixel in images.iter().flat_map(|x| x.pixels()) {
+        if !colors.contains(pixel) {
+            colors.push(*pixel);
+        }
+    }
+
+    let color_string = |color: &Rgb<u8>| format!("{:02X}{:02X}{:02X}", color[0], color[1], color[2]);
+    if colors.len() > 4 {
+        panic!(
+            "The reference tileset \"{}\" uses {} colors, but a palette can only contain 4 colors: {}",
+            path.imm_to_str(), colors.len(), colors.iter().map(color_string).collect::<Vec<String>>().join(", ")
+        );
+    }
+
+    if palette_order.is_empty() {
+        let luminance = |color: &Rgb<u8>| 299 * color[0] as u32 + 587 * color[1] as u32 + 114 * color[2] as u32;
+        colors.sort_by_key(|x| std::cmp::Reverse(luminance(x)));
+    } else {
+        if palette_order.len() > 4 {
+            panic!("The palette order contains {} colors, but a palette can only contain 4 colors", palette_order.len());
+        }
+        if let Some(unordered_color) = colors.iter().find(|x| !palette_order.contains(x)) {
+            panic!("The reference tileset \"{}\" uses the color {}, which isn't part of the palette order", path.imm_to_str(), color_string(unordered_color));
+        }
+        colors = palette_order.clone();
+    }
+
+    // Unused palette indices repeat the last color, since colors are matched against the palette from the first index on
+    let mut color_palette: ColorPalette = unsafe { std::mem::zeroed() };
+    for i in 0..4 {
+        color_palette[i] = *colors.get(i).or(colors.last()).unwrap_or(&Rgb([0, 0, 0]));
+    }
+    return color_palette;
+}
+
+/// Parses a color of the form RRGGBB (e.g. ff0000), optionally prefixed with #
+pub fn parse_rgb_color(str: &str) -> Result<Rgb<u8>, String> {
+    let color_str = str.trim_start_matches('#');
+    if color_str.len() != 6 {
+        return Err(format!("\"{}\" is not a color of the form RRGGBB", color_str));
+    }
+    let color = u32::from_str_radix(color_str, 16).map_err(|_| format!("\"{}\" is not a color of the form RRGGBB", color_str))?;
+    return Ok(Rgb([(color >> 16) as u8, (color >> 8) as u8, color as u8]));
+}
+
+/// Lists the tile images of a reference tileset directory, sorted by file name. The tiles of a subdirectory are
+/// inserted at the position of the subdirectory and receive the name of the subdirectory as a tile property
+/// (in addition to the properties of the directories above it).
+fn tile_image_paths_from_directory(path: &Path, properties: &Vec<String>) -> Vec<(PathBuf, Vec<String>)> {
+    let mut directory_entry_vec = std::fs::read_dir(path)
+    .expect(format!("Couldn't read reference tileset directory members from path: {}", path.imm_to_str()).as_str())
+    .map(|x| x.unwrap())
+    .collect::<Vec<std::fs::DirEntry>>();
+
+    directory_entry_vec.sort_by(|x, y| (x.file_name().to_str().unwrap()).cmp(
+        y.file_name().to_str().unwrap()
+    ));
+
+    let mut res = Vec::new();
+    for dir_entry in directory_entry_vec {
+        if dir_entry.path().is_dir() {
+            let mut subdirectory_properties = properties.clone();
+            subdirectory_properties.push(dir_entry.file_name().to_str().unwrap().to_string());
+            res.extend(tile_image_paths_from_directory(&dir_entry.path(), &subdirectory_properties));
+        } else {
+            res.push((dir_entry.path(), properties.clone()));
+        }
+    }
+
+    return res;
 }
 
 // Set up a hashmap that contains every version of a tile (original, x-flipped, y-flipped, x-flipped+y-flipped) and the corresponding tile index and flip information
-pub fn tile_searchmap_from_tiledata_vec(tile_info_vec: Vec<TileInfo>) -> TileSearchmap {
+pub fn tile_searchmap_from_tiledata_vec(tile_info_vec: &Vec<TileInfo>) -> TileSearchmap {
 
     let mut tile_searchmap: TileSearchmap= TileSearchmap(HashMap::new());
-    for (tile_index, tile_info) in tile_info_vec.into_iter().enumerate() {
+    for (tile_index, tile_info) in tile_info_vec.iter().enumerate() {
 
         for x_flip in 0..2 {
             for y_flip in 0..2 {
@@ -170,7 +305,7 @@ pub fn tile_searchmap_from_tiledata_vec(tile_info_vec: Vec<TileInfo>) -> TileSea
         }
 
         // Insert original tile
-        tile_searchmap.insert(tile_info.color_array, TileSearchmapValue {
+        tile_searchmap.insert(tile_info.color_array.clone(), TileSearchmapValue {
             x_flip: false,
             y_flip: false,
             tile_index: tile_index as u8,
@@ -182,7 +317,57 @@ pub fn tile_searchmap_from_tiledata_vec(tile_info_vec: Vec<TileInfo>) -> TileSea
     return tile_searchmap;
 }
 
-pub fn rgbimage_from_path(path: &Path) -> image::RgbImage {
+/// Returns the tile index that the tile at the tile position (tile_x, tile_y) inside a single image
+/// reference tileset receives. Tiles are read column by column (see read_tileset_info_from_path)
+/// and the reference tile at (0,0) (if there is one) doesn't receive an index, in which case None is returned.
+pub fn tile_index_from_tileset_image_position(tile_x: u32, tile_y: u32, image_height_in_tiles: u32, has_reference_tile: bool) -> Option<u8> {
+    let position = tile_x * image_height_in_tiles + tile_y;
+    if has_reference_tile && position == 0 {
+        return None;
+    }
+    let tile_index = position - (has_reference_tile as u32);
+    if tile_index > u8::MAX as u32 {
+        return None;
+    }
+    return Some(tile_index as u8);
+}
+
+/// Map editors (Tiled, LDtk) reference the tileset image they use. Since their tile ids are translated
+/// directly to the tile indices of the reference tileset, that image needs to be the reference tileset image.
+/// Returns the dimensions (in tiles) of the reference tileset image.
+pub fn reference_tileset_dimensions_in_tiles_for_editor_tileset(editor_tileset_image_path: &Path, reference_tileset_path: &Path, editor_file_path: &Path) -> (u32, u32) {
+    if reference_tileset_path.is_dir() {
+        panic!("Maps from map editors can only be used together with a reference tileset that is supplied as a single image (the tileset image used in the editor)");
+    }
+
+    let reference_tileset_canonical_path = reference_tileset_path.canonicalize()
+        .expect(format!("Couldn't resolve the reference tileset path \"{}\"", reference_tileset_path.imm_to_str()).as_str());
+    let editor_tileset_image_canonical_path = editor_tileset_image_path.canonicalize()
+        .expect(format!("Couldn't resolve the tileset image path \"{}\" used by \"{}\"", editor_tileset_image_path.imm_to_str(), editor_file_path.imm_to_str()).as_str());
+    if editor_tileset_image_canonical_path != reference_tileset_canonical_path {
+        panic!("\"{}\" uses the tileset image \"{}\", but only the reference tileset image \"{}\" can be used", editor_file_path.imm_to_str(), editor_tileset_image_path.imm_to_str(), reference_tileset_path.imm_to_str());
+    }
+
+    let (reference_tileset_image_width, reference_tileset_image_height) = image::image_dimensions(reference_tileset_path)
+        .expect(format!("Failed to open the path \"{}\"", reference_tileset_path.imm_to_str()).as_str());
+    return (reference_tileset_image_width / 8, reference_tileset_image_height / 8);
+}
+
+/// Returns the GBC background attribute byte for a tile with the given flips
+pub fn attribute_byte_from_flips(x_flip: bool, y_flip: bool) -> u8 {
+    let mut attribute_byte: u8 = 0;
+    //	7	        6	    5	        4	    3	    210
+    //	Priority	Y flip	X flip		/       Bank	Color palette
+    attribute_byte += (x_flip as u8) << 5;
+    attribute_byte += (y_flip as u8) << 6;
+    return attribute_byte;
+}
+
+pub fn rgbimage_from_path(path: &Path, aseprite_layer_selection: &AsepriteLayerSelection) -> image::RgbImage {
+    if is_aseprite_path(path) {
+        return rgbimage_from_aseprite_path(path, aseprite_layer_selection);
+    }
+
     let image: DynamicImage = image::open(
             path
     ).expect(format!("Failed to open the path \"{}\"", path.imm_to_str()).as_str() );
@@ -194,6 +379,120 @@ pub fn rgbimage_from_path(path: &Path) -> image::RgbImage {
 }
 
 
+/// Reads the image at path and replaces its colors, that aren't part of the palette, via the color matching
+/// and quantization settings
+pub fn palette_conforming_tile_source_image_from_path(path: &Path, use_palette_indices: bool, aseprite_layer_selection: &AsepriteLayerSelection, color_palette: &ColorPalette, color_matching_settings: Option<&ColorMatchingSettings>, quantization_settings: Option<&QuantizationSettings>) -> TileSourceImage {
+    let image = tile_source_image_from_path(path, use_palette_indices, aseprite_layer_selection);
+    let image = palette_matched_tile_source_image(image, color_palette, color_matching_settings, path);
+    return quantized_tile_source_image(image, color_palette, quantization_settings, path);
+}
+
+/// An image whose pixels are either colors that are matched against the color palette or palette indices
+/// that are used directly (for indexed images)
+pub enum TileSourceImage {
+    Rgb(image::RgbImage),
+    PaletteIndices(image::GrayImage),
+}
+
+impl TileSourceImage {
+    pub fn width(&self) -> u32 {
+        match self {
+            TileSourceImage::Rgb(image) => image.width(),
+            TileSourceImage::PaletteIndices(image) => image.width(),
+        }
+    }
+    pub fn height(&self) -> u32 {
+        match self {
+            TileSourceImage::Rgb(image) => image.height(),
+            TileSourceImage::PaletteIndices(image) => image.height(),
+        }
+    }
+}
+
+/// Reads the image at path. If use_palette_indices is set and the image is an indexed PNG, its palette
+/// indices are used instead of its colors
+pub fn tile_source_image_from_path(path: &Path, use_palette_indices: bool, aseprite_layer_selection: &AsepriteLayerSelection) -> TileSourceImage {
+    if use_palette_indices {
+        if let Some((palette_index_image, _)) = palette_index_image_from_path(path) {
+            return TileSourceImage::PaletteIndices(palette_index_image);
+        }
+    }
+    return TileSourceImage::Rgb(rgbimage_from_path(path, aseprite_layer_selection));
+}
+
+/// Reads the raw palette indices and the palette of an indexed (palette based) PNG image.
+/// Returns None for any other image.
+pub fn palette_index_image_from_path(path: &Path) -> Option<(image::GrayImage, Vec<Rgb<u8>>)> {
+    if path.extension().and_then(|x| x.to_str()).map(|x| x.to_lowercase()) != Some(String::from("png")) {
+        return None;
+    }
+    let file = std::fs::File::open(path).expect(format!("Failed to open the path \"{}\"", path.imm_to_str()).as_str());
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    // Don't expand the palette indices to colors
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info().expect(format!("Failed to decode the png image \"{}\"", path.imm_to_str()).as_str());
+
+    if reader.info().color_type != png::ColorType::Indexed {
+        return None;
+    }
+    let palette: Vec<Rgb<u8>> = reader.info().palette.as_ref()
+        .map(|x| x.chunks_exact(3).map(|color| Rgb([color[0], color[1], color[2]])).collect())
+        .unwrap_or(Vec::new());
+
+    let mut buffer = vec![0u8; reader.output_buffer_size().unwrap()];
+    let output_info = reader.next_frame(&mut buffer).expect(format!("Failed to decode the png image \"{}\"", path.imm_to_str()).as_str());
+    let bit_depth = output_info.bit_depth as u32;
+
+    // Palette indices with a bit depth below 8 are packed into bytes, starting with the most significant bits
+    let mut res = image::GrayImage::new(output_info.width, output_info.height);
+    for y in 0..output_info.height {
+        let line = &buffer[(y as usize * output_info.line_size)..((y as usize + 1) * output_info.line_size)];
+        for x in 0..output_info.width {
+            let bit_position = x * bit_depth;
+            let byte = line[(bit_position / 8) as usize];
+            let shift = 8 - bit_depth - (bit_position % 8);
+            let palette_index = (byte >> shift) & (((1u16 << bit_depth) - 1) as u8);
+            res.put_pixel(x, y, image::Luma([palette_index]));
+        }
+    }
+
+    return Some((res, palette));
+}
+
+/// The first four colors of an indexed image's palette make up the color palette (missing colors are black)
+pub fn color_palette_from_indexed_palette(indexed_palette: &Vec<Rgb<u8>>) -> ColorPalette {
+    let mut color_palette: ColorPalette = unsafe { std::mem::zeroed() };
+    for i in 0..4 {
+        color_palette[i] = *indexed_palette.get(i).unwrap_or(&Rgb([0, 0, 0]));
+    }
+    return color_palette;
+}
+
+pub fn read_tile_from_tile_source_image(tile_index_x: u32, tile_index_y: u32, image: &TileSourceImage, color_palette: &ColorPalette) -> TileColorArray {
+    match image {
+        TileSourceImage::Rgb(image) => read_tile_from_image(tile_index_x, tile_index_y, image, color_palette),
+        TileSourceImage::PaletteIndices(image) => read_tile_from_palette_index_image(tile_index_x, tile_index_y, image),
+    }
+}
+
+pub fn read_tile_from_palette_index_image(tile_index_x: u32, tile_index_y: u32, image: &image::GrayImage) -> TileColorArray {
+    let mut tile_color_array: TileColorArray = unsafe { std::mem::zeroed() };
+
+    for x in 0..8 {
+        for y in 0..8 {
+            let cur_pixel_x: u32 = tile_index_x * 8 + x as u32;
+            let cur_pixel_y: u32 = tile_index_y * 8 + y as u32;
+            let palette_index = image.get_pixel(cur_pixel_x, cur_pixel_y)[0];
+            if palette_index > 3 {
+                panic!("Data tiles in indexed image use the palette index {}, but only the palette indices 0-3 are allowed (Error at tile: ({},{}) and relative pixel coordinates: ({},{}) i.e. ({},{}) in absolute pixel coordinates", palette_index, tile_index_x, tile_index_y, x, y, cur_pixel_x, cur_pixel_y);
+            }
+            tile_color_array.assign(x, y, palette_index);
+        }
+    }
+
+    return tile_color_array;
+}
+
 pub fn read_tile_from_image(tile_index_x: u32, tile_index_y: u32, image: &image::RgbImage, color_palette: &ColorPalette) -> TileColorArray {
     let mut tile_color_array: TileColorArray;
     unsafe {
@@ -222,9 +521,9 @@ pub fn read_tile_from_image(tile_index_x: u32, tile_index_y: u32, image: &image:
     return tile_color_array;
 }
 
-pub fn index_and_attribute_array_from_tilemap_image_path(tilemap_image_path: &Path, color_palette: &ColorPalette, tile_search_map: &HashMap<TileColorArray, TileSearchmapValue> ,allow_attributes_and_generate_attribute_array: bool) -> (TileIndexArray, Option<AttributeByteArray>) {
+pub fn index_and_attribute_array_from_tilemap_image_path(tilemap_image_path: &Path, color_palette: &ColorPalette, tile_search_map: &HashMap<TileColorArray, TileSearchmapValue> ,allow_attributes_and_generate_attribute_array: bool, use_palette_indices: bool, aseprite_layer_selection: &AsepriteLayerSelection, color_matching_settings: Option<&ColorMatchingSettings>, quantization_settings: Option<&QuantizationSettings>) -> (TileIndexArray, Option<AttributeByteArray>, TileSourceImage) {
 
-    let tilemap_image = rgbimage_from_path(tilemap_image_path);
+    let tilemap_image = palette_conforming_tile_source_image_from_path(tilemap_image_path, use_palette_indices, aseprite_layer_selection, color_palette, color_matching_settings, quantization_settings);
 
     // sanity checks on image dimensions
     if((tilemap_image.width() % 8) > 0) || ((tilemap_image.height() % 8) > 0) {
@@ -244,13 +543,13 @@ pub fn index_and_attribute_array_from_tilemap_image_path(tilemap_image_path: &Pa
     let mut tile_index_array: TileIndexArray = unsafe { std::mem::zeroed()};
 
     let mut attributes_byte_array: Option<AttributeByteArray> = match allow_attributes_and_generate_attribute_array {
-        true => Some(unsafe {std::mem::uninitialized::<AttributeByteArray>()}),
+        true => Some(unsafe {std::mem::zeroed::<AttributeByteArray>()}),
         false => None
     };
 
     for x in 0..tilemap_width {
         for y in 0..tilemap_height {
-            let current_tile_tiledata = read_tile_from_image(x,y,&tilemap_image, color_palette);
+            let current_tile_tiledata = read_tile_from_tile_source_image(x,y,&tilemap_image, color_palette);
             match tile_search_map.get(&current_tile_tiledata) {
                 Some(searchmap_value) => { // found matching tile in search map. Not all matches are allowed though depending on allow_attributes_and_generate_attribute_vector
                     let mut current_chain_link: &TileSearchmapValue = searchmap_value;
@@ -290,7 +589,7 @@ pub fn index_and_attribute_array_from_tilemap_image_path(tilemap_image_path: &Pa
         }
     } 
 
-    return (tile_index_array, attributes_byte_array);
+    return (tile_index_array, attributes_byte_array, tilemap_image);
 }
 
 
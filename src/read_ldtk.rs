@@ -0,0 +1,228 @@
+// This module imports the levels of LDtk projects (.ldtk files). Like with Tiled maps, the tiles placed in
+// tile layers (and auto layers) are translated directly to tile indices of the reference tileset, whose image
+// needs to be the tileset image used in LDtk. IntGrid values and entity positions are exported as well.
+
+use std::path::Path;
+
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+use crate::read_input::*;
+
+/// A tile layer (or auto layer) of a LDtk level converted to the gameboy format
+pub struct LdtkTileLayer {
+    pub name: String,
+    pub index_array: TileIndexArray,
+    pub attribute_array: Option<AttributeByteArray>,
+}
+
+/// The values of an IntGrid layer of a LDtk level (0 marks an empty cell)
+pub struct LdtkIntGridLayer {
+    pub name: String,
+    pub value_array: TilemapByteArray,
+}
+
+pub struct LdtkEntity {
+    pub name: String,
+    // index of the entity definition in the project, so that every entity type receives a constant
+    pub type_index: usize,
+    pub tile_x: u32,
+    pub tile_y: u32,
+    pub pixel_x: u32,
+    pub pixel_y: u32,
+}
+
+pub struct LdtkLevel {
+    pub name: String,
+    pub tile_layers: Vec<LdtkTileLayer>,
+    pub int_grid_layers: Vec<LdtkIntGridLayer>,
+    // (layer name, entities)
+    pub entity_layers: Vec<(String, Vec<LdtkEntity>)>,
+}
+
+pub struct LdtkProject {
+    pub entity_type_names: Vec<String>,
+    pub levels: Vec<LdtkLevel>,
+}
+
+/// Reads every level of the LDtk project at ldtk_project_path.
+pub fn ldtk_project_from_path(ldtk_project_path: &Path, reference_tileset_path: &Path, tileset_has_reference_tile: bool, allow_attributes_and_generate_attribute_array: bool) -> LdtkProject {
+    let root = json_value_from_path(ldtk_project_path);
+
+    let entity_type_names: Vec<String> = root["defs"]["entities"].as_array()
+        .map(|x| x.iter().map(|entity| entity["identifier"].as_str().unwrap_or("entity").to_string()).collect())
+        .unwrap_or(Vec::new());
+    let entity_uids: Vec<u64> = root["defs"]["entities"].as_array()
+        .map(|x| x.iter().map(|entity| entity["uid"].as_u64().unwrap_or(0)).collect())
+        .unwrap_or(Vec::new());
+
+    // Check the tileset definitions once, every tileset needs to be the reference tileset
+    let mut reference_tileset_dimensions_in_tiles = None;
+    for tileset in root["defs"]["tilesets"].as_array().unwrap_or(&Vec::new()) {
+        // Internal tilesets (e.g. LDtk's icons) don't have an image
+        let Some(relative_path) = tileset["relPath"].as_str() else {
+            continue;
+        };
+        if tileset["tileGridSize"].as_u64() != Some(8) {
+            panic!("Tileset \"{}\" of the LDtk project \"{}\" doesn't use 8x8 tiles", relative_path, ldtk_project_path.imm_to_str());
+        }
+        if tileset["spacing"].as_u64().unwrap_or(0) != 0 || tileset["padding"].as_u64().unwrap_or(0) != 0 {
+            panic!("Tileset \"{}\" of the LDtk project \"{}\" uses spacing or padding, which isn't supported", relative_path, ldtk_project_path.imm_to_str());
+        }
+        reference_tileset_dimensions_in_tiles = Some(reference_tileset_dimensions_in_tiles_for_editor_tileset(
+            &path_relative_to_file(ldtk_project_path, relative_path), reference_tileset_path, ldtk_project_path
+        ));
+    }
+
+    // Levels are either stored directly in the project or in the worlds of a multi world project
+    let mut level_values: Vec<serde_json::Value> = Vec::new();
+    let mut collect_level_values = |levels: &serde_json::Value| {
+        for level in levels.as_array().unwrap_or(&Vec::new()) {
+            // Levels might be saved in separate files
+            match level["externalRelPath"].as_str() {
+                Some(external_relative_path) => level_values.push(json_value_from_path(&path_relative_to_file(ldtk_project_path, external_relative_path))),
+                None => level_values.push(level.clone()),
+            }
+        }
+    };
+    collect_level_values(&root["levels"]);
+    for world in root["worlds"].as_array().unwrap_or(&Vec::new()) {
+        collect_level_values(&world["levels"]);
+    }
+
+    let mut levels = Vec::new();
+    for level in level_values {
+        let level_name = level["identifier"].as_str().unwrap_or("level").to_string();
+        let mut tile_layers = Vec::new();
+        let mut int_grid_layers = Vec::new();
+        let mut entity_layers = Vec::new();
+
+        let Some(layer_instances) = level["layerInstances"].as_array() else {
+            panic!("Level \"{}\" of the LDtk project \"{}\" contains no layer instances", level_name, ldtk_project_path.imm_to_str());
+        };
+
+        for layer in layer_instances {
+            let layer_name = layer["__identifier"].as_str().unwrap_or("layer").to_string();
+            let grid_size = layer["__gridSize"].as_u64().unwrap_or(0) as u32;
+            let error_location = format!("Layer \"{}\" of the level \"{}\" of the LDtk project \"{}\"", layer_name, level_name, ldtk_project_path.imm_to_str());
+            let (width, height) = match (layer["__cWid"].as_u64(), layer["__cHei"].as_u64()) {
+                (Some(width), Some(height)) if width > 0 && height > 0 => (width as u32, height as u32),
+                _ => panic!("{} has no valid size (__cWid and __cHei have to be positive integers)", error_location),
+            };
+
+            if (width > 32) || (height > 32) {
+                panic!("{} is too big (max size: 32x32)", error_location);
+            }
+
+            if layer["__type"].as_str() == Some("IntGrid") {
+                let mut value_array: TilemapByteArray = unsafe { std::mem::zeroed() };
+                for (cell_index, value) in layer["intGridCsv"].as_array().unwrap_or(&Vec::new()).iter().enumerate() {
+                    let value = value.as_u64().unwrap_or(0);
+                    if value > u8::MAX as u64 {
+                        panic!("{} contains the IntGrid value {}, which doesn't fit into a byte", error_location, value);
+                    }
+                    value_array.assign(cell_index as u32 % width, cell_index as u32 / width, value as u8);
+                }
+                int_grid_layers.push(LdtkIntGridLayer {
+                    name: layer_name.clone(),
+                    value_array: value_array,
+                });
+            }
+
+            if layer["__type"].as_str() == Some("Entities") {
+                let mut entities = Vec::new();
+                for entity in layer["entityInstances"].as_array().unwrap_or(&Vec::new()) {
+                    let entity_name = entity["__identifier"].as_str().unwrap_or("entity").to_string();
+                    let Some(type_index) = entity["defUid"].as_u64().and_then(|definition_uid| entity_uids.iter().position(|x| *x == definition_uid)) else {
+                        panic!("{} contains the entity \"{}\", whose definition (defUid) isn't defined in the project", error_location, entity_name);
+                    };
+                    let grid_position = &entity["__grid"];
+                    let pixel_position = &entity["px"];
+                    entities.push(LdtkEntity {
+                        name: entity_name,
+                        type_index: type_index,
+                        tile_x: grid_position[0].as_u64().unwrap_or(0) as u32,
+                        tile_y: grid_position[1].as_u64().unwrap_or(0) as u32,
+                        pixel_x: pixel_position[0].as_u64().unwrap_or(0) as u32,
+                        pixel_y: pixel_position[1].as_u64().unwrap_or(0) as u32,
+                    });
+                }
+                entity_layers.push((layer_name.clone(), entities));
+            }
+
+            // IntGrid layers store their values, auto layers and IntGrid layers with auto rules store their tiles
+            // in autoLayerTiles, tile layers store them in gridTiles
+            let tiles = match layer["__type"].as_str() {
+                Some("Tiles") => layer["gridTiles"].as_array(),
+                _ => layer["autoLayerTiles"].as_array().filter(|x| !x.is_empty()),
+            };
+            let Some(tiles) = tiles else {
+                continue;
+            };
+
+            if grid_size != 8 {
+                panic!("{} doesn't use a grid size of 8", error_location);
+            }
+            let Some((reference_tileset_width_in_tiles, reference_tileset_height_in_tiles)) = reference_tileset_dimensions_in_tiles else {
+                panic!("{} contains tiles, but the project doesn't define a tileset", error_location);
+            };
+
+            let mut index_array: TileIndexArray = unsafe { std::mem::zeroed() };
+            let mut attribute_array: Option<AttributeByteArray> = match allow_attributes_and_generate_attribute_array {
+                true => Some(unsafe { std::mem::zeroed() }),
+                false => None,
+            };
+
+            for tile in tiles {
+                let x = tile["px"][0].as_u64().unwrap_or(0) as u32 / 8;
+                let y = tile["px"][1].as_u64().unwrap_or(0) as u32 / 8;
+                let tileset_tile_x = tile["src"][0].as_u64().unwrap_or(0) as u32 / 8;
+                let tileset_tile_y = tile["src"][1].as_u64().unwrap_or(0) as u32 / 8;
+                // bit 0: x-flip, bit 1: y-flip
+                let flip_bits = tile["f"].as_u64().unwrap_or(0);
+                let x_flip = (flip_bits & 1) != 0;
+                let y_flip = (flip_bits & 2) != 0;
+
+                if (x >= width) || (y >= height) {
+                    continue;
+                }
+                if (x_flip || y_flip) && (!allow_attributes_and_generate_attribute_array) {
+                    panic!("{} contains a flipped tile at the tile index: ({},{}). Only the GBC allows for flipped tiles via an additional attribute byte tilemap space in VRAM. Consider using the --ldtk_mwa parameter instead", error_location, x, y);
+                }
+                if (tileset_tile_x >= reference_tileset_width_in_tiles) || (tileset_tile_y >= reference_tileset_height_in_tiles) {
+                    panic!("{} contains a tile that lies outside of the reference tileset image at the tile index: ({},{})", error_location, x, y);
+                }
+                let Some(tile_index) = tile_index_from_tileset_image_position(tileset_tile_x, tileset_tile_y, reference_tileset_height_in_tiles, tileset_has_reference_tile) else {
+                    panic!("{} uses the reference tile (the palette tile in the upper left corner of the tileset) or a tile beyond the first 256 tiles at the tile index: ({},{})", error_location, x, y);
+                };
+
+                index_array.assign(x, y, tile_index);
+                if let Some(byte_array) = attribute_array.as_mut() {
+                    byte_array.assign(x, y, attribute_byte_from_flips(x_flip, y_flip));
+                }
+            }
+
+            tile_layers.push(LdtkTileLayer {
+                name: layer_name,
+                index_array: index_array,
+                attribute_array: attribute_array,
+            });
+        }
+
+        levels.push(LdtkLevel {
+            name: level_name,
+            tile_layers: tile_layers,
+            int_grid_layers: int_grid_layers,
+            entity_layers: entity_layers,
+        });
+    }
+
+    return LdtkProject {
+        entity_type_names: entity_type_names,
+        levels: levels,
+    };
+}
+
+fn json_value_from_path(path: &Path) -> serde_json::Value {
+    let file_content = std::fs::read_to_string(path).expect(format!("Failed to read the file \"{}\"", path.imm_to_str()).as_str());
+    return serde_json::from_str(file_content.as_str()).expect(format!("\"{}\" is not valid json", path.imm_to_str()).as_str());
+}
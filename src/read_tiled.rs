@@ -0,0 +1,304 @@
+// This module imports tile layers from maps created with the Tiled map editor (.tmx or .tmj/.json files).
+// Tiled already stores which tile of its tileset is used at every map position, so instead of matching
+// the pixels of the map against the reference tileset, the global tile ids (and flip flags) are translated
+// directly to tile indices (and attribute bytes).
+
+use std::path::{Path, PathBuf};
+
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+use crate::read_input::*;
+
+// Flags that Tiled stores in the highest bits of a global tile id
+const TILED_FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
+const TILED_FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
+const TILED_FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
+const TILED_ROTATED_HEXAGONAL_120_FLAG: u32 = 0x10000000;
+const TILED_ALL_FLAGS: u32 = TILED_FLIPPED_HORIZONTALLY_FLAG | TILED_FLIPPED_VERTICALLY_FLAG | TILED_FLIPPED_DIAGONALLY_FLAG | TILED_ROTATED_HEXAGONAL_120_FLAG;
+
+/// A single tile layer of a Tiled map (e.g. background, window or collision) converted to the gameboy format
+pub struct TiledLayer {
+    pub name: String,
+    pub index_array: TileIndexArray,
+    pub attribute_array: Option<AttributeByteArray>,
+}
+
+struct TiledTileset {
+    first_gid: u32,
+    image_path: PathBuf,
+}
+
+struct TiledMap {
+    width: u32,
+    height: u32,
+    tilesets: Vec<TiledTileset>,
+    // (layer name, global tile ids in row major order)
+    layers: Vec<(String, Vec<u32>)>,
+}
+
+/// Reads every tile layer of the Tiled map at tiled_map_path. All tilesets used by the map need to use the
+/// (single image) reference tileset as their image, since the tile ids of Tiled are translated to the tile
+/// indices that ITGBA assigns to the tiles of the reference tileset.
+pub fn tiled_layers_from_tiled_map_path(tiled_map_path: &Path, reference_tileset_path: &Path, tileset_has_reference_tile: bool, allow_attributes_and_generate_attribute_array: bool) -> Vec<TiledLayer> {
+    let tiled_map = match tiled_map_path.extension().and_then(|x| x.to_str()) {
+        Some("tmx") => tiled_map_from_tmx_path(tiled_map_path),
+        Some("tmj") | Some("json") => tiled_map_from_tmj_path(tiled_map_path),
+        _ => panic!("Tiled map \"{}\" needs to have a .tmx, .tmj or .json extension", tiled_map_path.imm_to_str()),
+    };
+
+    if (tiled_map.width > 32) || (tiled_map.height > 32) {
+        panic!("Tiled map from the path \"{}\" is too big (max size: 32x32)", tiled_map_path.imm_to_str());
+    }
+
+    let mut reference_tileset_dimensions_in_tiles = None;
+    for tileset in tiled_map.tilesets.iter() {
+        reference_tileset_dimensions_in_tiles = Some(
+            reference_tileset_dimensions_in_tiles_for_editor_tileset(&tileset.image_path, reference_tileset_path, tiled_map_path)
+        );
+    }
+    let Some((reference_tileset_width_in_tiles, reference_tileset_height_in_tiles)) = reference_tileset_dimensions_in_tiles else {
+        panic!("Tiled map \"{}\" doesn't use any tileset", tiled_map_path.imm_to_str());
+    };
+
+    let mut res = Vec::new();
+
+    for (layer_name, global_tile_ids) in tiled_map.layers {
+        if global_tile_ids.len() != (tiled_map.width * tiled_map.height) as usize {
+            panic!("Layer \"{}\" of the Tiled map \"{}\" doesn't contain width*height tiles", layer_name, tiled_map_path.imm_to_str());
+        }
+
+        let mut index_array: TileIndexArray = unsafe { std::mem::zeroed() };
+        let mut attribute_array: Option<AttributeByteArray> = match allow_attributes_and_generate_attribute_array {
+            true => Some(unsafe { std::mem::zeroed() }),
+            false => None,
+        };
+
+        for y in 0..tiled_map.height {
+            for x in 0..tiled_map.width {
+                let global_tile_id_with_flags = global_tile_ids[(y * tiled_map.width + x) as usize];
+                let global_tile_id = global_tile_id_with_flags & !TILED_ALL_FLAGS;
+
+                // A global tile id of 0 marks an empty cell, which receives the default 0 index
+                if global_tile_id == 0 {
+                    continue;
+                }
+
+                let x_flip = (global_tile_id_with_flags & TILED_FLIPPED_HORIZONTALLY_FLAG) != 0;
+                let y_flip = (global_tile_id_with_flags & TILED_FLIPPED_VERTICALLY_FLAG) != 0;
+
+                if (global_tile_id_with_flags & TILED_FLIPPED_DIAGONALLY_FLAG) != 0 {
+                    panic!("Layer \"{}\" of the Tiled map \"{}\" contains a rotated (diagonally flipped) tile at the tile index: ({},{}). The gameboy only supports x-flips and y-flips", layer_name, tiled_map_path.imm_to_str(), x, y);
+                }
+                if (x_flip || y_flip) && (!allow_attributes_and_generate_attribute_array) {
+                    panic!("Layer \"{}\" of the Tiled map \"{}\" contains a flipped tile at the tile index: ({},{}). Only the GBC allows for flipped tiles via an additional attribute byte tilemap space in VRAM. Consider using the --tiled_mwa parameter instead", layer_name, tiled_map_path.imm_to_str(), x, y);
+                }
+
+                // The tileset with the highest first gid that is not bigger than the global tile id contains the tile
+                let Some(tileset) = tiled_map.tilesets.iter().filter(|tileset| tileset.first_gid <= global_tile_id).max_by_key(|tileset| tileset.first_gid) else {
+                    panic!("Layer \"{}\" of the Tiled map \"{}\" contains the tile id {} that doesn't belong to any tileset", layer_name, tiled_map_path.imm_to_str(), global_tile_id);
+                };
+                // Tiled numbers the tiles of a tileset image from left-to-right, top-to-bottom
+                let local_tile_id = global_tile_id - tileset.first_gid;
+                let tile_x = local_tile_id % reference_tileset_width_in_tiles;
+                let tile_y = local_tile_id / reference_tileset_width_in_tiles;
+                if tile_y >= reference_tileset_height_in_tiles {
+                    panic!("Layer \"{}\" of the Tiled map \"{}\" contains the tile id {} that lies outside of the reference tileset image", layer_name, tiled_map_path.imm_to_str(), global_tile_id);
+                }
+
+                let Some(tile_index) = tile_index_from_tileset_image_position(tile_x, tile_y, reference_tileset_height_in_tiles, tileset_has_reference_tile) else {
+                    panic!("Layer \"{}\" of the Tiled map \"{}\" uses the reference tile (the palette tile in the upper left corner of the tileset) or a tile beyond the first 256 tiles at the tile index: ({},{})", layer_name, tiled_map_path.imm_to_str(), x, y);
+                };
+
+                index_array.assign(x, y, tile_index);
+
+                if let Some(byte_array) = attribute_array.as_mut() {
+                    byte_array.assign(x, y, attribute_byte_from_flips(x_flip, y_flip));
+                }
+            }
+        }
+
+        res.push(TiledLayer {
+            name: layer_name,
+            index_array: index_array,
+            attribute_array: attribute_array,
+        });
+    }
+
+    if res.is_empty() {
+        print_warning(format!("Tiled map \"{}\" doesn't contain any tile layers", tiled_map_path.imm_to_str()).as_str());
+    }
+
+    return res;
+}
+
+fn check_tiled_tileset_geometry(tile_width: u64, tile_height: u64, margin: u64, spacing: u64, tileset_source: &Path) {
+    if tile_width != 8 || tile_height != 8 {
+        panic!("Tileset used by \"{}\" doesn't use 8x8 tiles", tileset_source.imm_to_str());
+    }
+    if margin != 0 || spacing != 0 {
+        panic!("Tileset used by \"{}\" uses a margin or spacing, which isn't supported", tileset_source.imm_to_str());
+    }
+}
+
+fn read_file_to_string(path: &Path) -> String {
+    std::fs::read_to_string(path).expect(format!("Failed to read the file \"{}\"", path.imm_to_str()).as_str())
+}
+
+fn tiled_map_from_tmj_path(path: &Path) -> TiledMap {
+    let root: serde_json::Value = serde_json::from_str(read_file_to_string(path).as_str())
+        .expect(format!("Tiled map \"{}\" is not valid json", path.imm_to_str()).as_str());
+
+    if root["infinite"].as_bool().unwrap_or(false) {
+        panic!("Tiled map \"{}\" is an infinite map, which isn't supported", path.imm_to_str());
+    }
+
+    let mut tilesets = Vec::new();
+    for tileset in root["tilesets"].as_array().expect(format!("Tiled map \"{}\" has no tilesets", path.imm_to_str()).as_str()) {
+        let first_gid = tileset["firstgid"].as_u64().expect("Tiled tileset without firstgid") as u32;
+
+        let image_path = match tileset["source"].as_str() {
+            Some(source) => {
+                let tileset_path = path_relative_to_file(path, source);
+                if tileset_path.extension().and_then(|x| x.to_str()) == Some("tsx") {
+                    tileset_image_path_from_tsx_path(&tileset_path)
+                } else {
+                    let external_tileset: serde_json::Value = serde_json::from_str(read_file_to_string(&tileset_path).as_str())
+                        .expect(format!("Tiled tileset \"{}\" is not valid json", tileset_path.imm_to_str()).as_str());
+                    tileset_image_path_from_tsj_value(&external_tileset, &tileset_path)
+                }
+            },
+            None => tileset_image_path_from_tsj_value(tileset, path),
+        };
+
+        tilesets.push(TiledTileset {
+            first_gid: first_gid,
+            image_path: image_path,
+        });
+    }
+
+    let mut layers = Vec::new();
+    collect_tmj_tile_layers(&root["layers"], path, &mut layers);
+
+    return TiledMap {
+        width: root["width"].as_u64().expect("Tiled map without width") as u32,
+        height: root["height"].as_u64().expect("Tiled map without height") as u32,
+        tilesets: tilesets,
+        layers: layers,
+    };
+}
+
+fn tileset_image_path_from_tsj_value(tileset: &serde_json::Value, tileset_source: &Path) -> PathBuf {
+    check_tiled_tileset_geometry(
+        tileset["tilewidth"].as_u64().unwrap_or(0),
+        tileset["tileheight"].as_u64().unwrap_or(0),
+        tileset["margin"].as_u64().unwrap_or(0),
+        tileset["spacing"].as_u64().unwrap_or(0),
+        tileset_source,
+    );
+    let Some(image) = tileset["image"].as_str() else {
+        panic!("Tileset used by \"{}\" is not based on a single tileset image", tileset_source.imm_to_str());
+    };
+    return path_relative_to_file(tileset_source, image);
+}
+
+// Group layers are flattened, so that every tile layer ends up in the output
+fn collect_tmj_tile_layers(layers: &serde_json::Value, path: &Path, res: &mut Vec<(String, Vec<u32>)>) {
+    let Some(layers) = layers.as_array() else {
+        return;
+    };
+    for layer in layers {
+        match layer["type"].as_str() {
+            Some("tilelayer") => {
+                let name = layer["name"].as_str().unwrap_or("layer").to_string();
+                let Some(data) = layer["data"].as_array() else {
+                    panic!("Layer \"{}\" of the Tiled map \"{}\" isn't stored as a plain array. Please set the tile layer format to CSV in the map properties", name, path.imm_to_str());
+                };
+                let global_tile_ids = data.iter().map(|x| x.as_u64().expect("Tiled tile layer contains a non integer tile id") as u32).collect();
+                res.push((name, global_tile_ids));
+            },
+            Some("group") => collect_tmj_tile_layers(&layer["layers"], path, res),
+            _ => {},
+        }
+    }
+}
+
+fn tiled_map_from_tmx_path(path: &Path) -> TiledMap {
+    let file_content = read_file_to_string(path);
+    let document = roxmltree::Document::parse(file_content.as_str())
+        .expect(format!("Tiled map \"{}\" is not valid xml", path.imm_to_str()).as_str());
+    let map_node = document.root_element();
+
+    if map_node.attribute("infinite") == Some("1") {
+        panic!("Tiled map \"{}\" is an infinite map, which isn't supported", path.imm_to_str());
+    }
+
+    let mut tilesets = Vec::new();
+    for tileset_node in map_node.children().filter(|x| x.has_tag_name("tileset")) {
+        let first_gid: u32 = tileset_node.attribute("firstgid").and_then(|x| x.parse().ok()).expect("Tiled tileset without firstgid");
+
+        let image_path = match tileset_node.attribute("source") {
+            Some(source) => {
+                let tileset_path = path_relative_to_file(path, source);
+                tileset_image_path_from_tsx_path(&tileset_path)
+            },
+            None => tileset_image_path_from_tsx_node(tileset_node, path),
+        };
+
+        tilesets.push(TiledTileset {
+            first_gid: first_gid,
+            image_path: image_path,
+        });
+    }
+
+    let mut layers = Vec::new();
+    for layer_node in map_node.descendants().filter(|x| x.has_tag_name("layer")) {
+        let name = layer_node.attribute("name").unwrap_or("layer").to_string();
+        let Some(data_node) = layer_node.children().find(|x| x.has_tag_name("data")) else {
+            panic!("Layer \"{}\" of the Tiled map \"{}\" contains no data", name, path.imm_to_str());
+        };
+
+        let global_tile_ids: Vec<u32> = match data_node.attribute("encoding") {
+            Some("csv") => data_node.text().unwrap_or("")
+                .split(',')
+                .map(|x| x.trim())
+                .filter(|x| !x.is_empty())
+                .map(|x| x.parse().expect("Tiled tile layer contains a non integer tile id"))
+                .collect(),
+            None => data_node.children()
+                .filter(|x| x.has_tag_name("tile"))
+                .map(|x| x.attribute("gid").map(|x| x.parse().expect("Tiled tile layer contains a non integer tile id")).unwrap_or(0))
+                .collect(),
+            Some(_) => panic!("Layer \"{}\" of the Tiled map \"{}\" uses an unsupported encoding. Please set the tile layer format to CSV in the map properties", name, path.imm_to_str()),
+        };
+        layers.push((name, global_tile_ids));
+    }
+
+    return TiledMap {
+        width: map_node.attribute("width").and_then(|x| x.parse().ok()).expect("Tiled map without width"),
+        height: map_node.attribute("height").and_then(|x| x.parse().ok()).expect("Tiled map without height"),
+        tilesets: tilesets,
+        layers: layers,
+    };
+}
+
+fn tileset_image_path_from_tsx_path(path: &Path) -> PathBuf {
+    let file_content = read_file_to_string(path);
+    let document = roxmltree::Document::parse(file_content.as_str())
+        .expect(format!("Tiled tileset \"{}\" is not valid xml", path.imm_to_str()).as_str());
+    return tileset_image_path_from_tsx_node(document.root_element(), path);
+}
+
+fn tileset_image_path_from_tsx_node(tileset_node: roxmltree::Node, tileset_source: &Path) -> PathBuf {
+    let attribute_as_u64 = |name: &str| tileset_node.attribute(name).and_then(|x| x.parse::<u64>().ok()).unwrap_or(0);
+    check_tiled_tileset_geometry(
+        attribute_as_u64("tilewidth"),
+        attribute_as_u64("tileheight"),
+        attribute_as_u64("margin"),
+        attribute_as_u64("spacing"),
+        tileset_source,
+    );
+    let Some(image) = tileset_node.children().find(|x| x.has_tag_name("image")).and_then(|x| x.attribute("source")) else {
+        panic!("Tileset used by \"{}\" is not based on a single tileset image", tileset_source.imm_to_str());
+    };
+    return path_relative_to_file(tileset_source, image);
+}
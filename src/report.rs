@@ -0,0 +1,124 @@
+// This module summarizes the sizes and the VRAM usage of the generated assets, either as a table for the
+// terminal or as JSON for build dashboards.
+
+use std::collections::BTreeSet;
+
+use crate::banking::*;
+use crate::output::*;
+
+/// Number of tiles in each of the three VRAM tile blocks (0x8000, 0x8800 and 0x9000)
+const VRAM_BLOCK_TILE_COUNT: usize = 128;
+
+/// Statistics of a single asset (output file), that are collected while its content is written
+#[derive(Default)]
+pub struct AssetReport {
+    pub tile_count: usize,
+    pub tile_data_size: usize,
+    pub map_data_size: usize,
+    // Tile indices of the tiles of a tileset or the tiles that are used by maps
+    pub used_tile_indices: BTreeSet<usize>,
+    // GBC palette numbers (attribute bits 0-2) that the attribute arrays of maps refer to
+    pub used_palettes: BTreeSet<u8>,
+    // Number of distinct metatiles of the metatile maps and the size of the tile index (and attribute) arrays,
+    // that the metatile maps replace
+    pub metatile_count: usize,
+    pub metatile_replaced_map_data_size: usize,
+}
+
+impl AssetReport {
+    /// Number of tile slots that the used tile indices occupy in each of the three VRAM blocks, if the tiles are
+    /// loaded starting with tile index 0 and the background uses the 0x8000 addressing mode (LCDC bit 4 set)
+    pub fn vram_block_tile_slots(&self) -> [usize; 3] {
+        let mut res = [0; 3];
+        for tile_index in self.used_tile_indices.iter().filter(|x| **x < 3 * VRAM_BLOCK_TILE_COUNT) {
+            res[tile_index / VRAM_BLOCK_TILE_COUNT] += 1;
+        }
+        return res;
+    }
+
+    /// Number of used tile indices that lie beyond the three VRAM blocks
+    pub fn vram_overflow_tile_count(&self) -> usize {
+        self.used_tile_indices.iter().filter(|x| **x >= 3 * VRAM_BLOCK_TILE_COUNT).count()
+    }
+}
+
+/// Returns a table that contains a row for every asset
+pub fn report_table_string(output: &Output) -> String {
+    let header = ["asset", "tiles", "tile data", "map data", "metatiles (replaced)", "VRAM 0/1/2", "palettes", "uncompressed", "ROM size", "bank"];
+    let mut rows: Vec<[String; 10]> = Vec::new();
+
+    for output_info in output.iter() {
+        let report = &output_info.report;
+        let vram_block_tile_slots = report.vram_block_tile_slots();
+        let vram_overflow_string = match report.vram_overflow_tile_count() {
+            0 => String::new(),
+            vram_overflow_tile_count => format!(" (+{})", vram_overflow_tile_count),
+        };
+        rows.push([
+            output_info.filename_without_extension_string(),
+            report.tile_count.to_string(),
+            report.tile_data_size.to_string(),
+            report.map_data_size.to_string(),
+            match report.metatile_count {
+                0 => String::from("-"),
+                metatile_count => format!("{} ({})", metatile_count, report.metatile_replaced_map_data_size),
+            },
+            format!("{}/{}/{}{}", vram_block_tile_slots[0], vram_block_tile_slots[1], vram_block_tile_slots[2], vram_overflow_string),
+            report.used_palettes.len().to_string(),
+            output_info.uncompressed_rom_data_size.to_string(),
+            output_info.rom_data_size.to_string(),
+            rom_bank_string(output_info.rom_bank),
+        ]);
+    }
+
+    // Every column is as wide as its widest cell
+    let column_widths: Vec<usize> = (0..header.len())
+        .map(|column| rows.iter().map(|x| x[column].len()).chain(std::iter::once(header[column].len())).max().unwrap())
+        .collect();
+    let format_row = |cells: Vec<&str>| -> String {
+        cells.iter().zip(column_widths.iter()).map(|(cell, width)| format!("{:<1$}", cell, width)).collect::<Vec<String>>().join(" | ").trim_end().to_string() + "\n"
+    };
+
+    let mut res = format_row(header.to_vec());
+    res.push_str(format_row(column_widths.iter().map(|x| "-".repeat(*x)).collect::<Vec<String>>().iter().map(|x| x.as_str()).collect()).as_str());
+    for row in rows.iter() {
+        res.push_str(format_row(row.iter().map(|x| x.as_str()).collect()).as_str());
+    }
+
+    return res;
+}
+
+/// Returns the report of every asset as a JSON array
+pub fn report_json_string(output: &Output) -> String {
+    let assets: Vec<serde_json::Value> = output.iter().map(|output_info| {
+        let report = &output_info.report;
+        serde_json::json!({
+            "asset": output_info.filename_without_extension_string(),
+            "tile_count": report.tile_count,
+            "tile_data_size": report.tile_data_size,
+            "map_data_size": report.map_data_size,
+            "metatile_count": report.metatile_count,
+            "metatile_replaced_map_data_size": report.metatile_replaced_map_data_size,
+            "vram_block_tile_slots": report.vram_block_tile_slots(),
+            "vram_overflow_tile_count": report.vram_overflow_tile_count(),
+            "palette_count": report.used_palettes.len(),
+            "uncompressed_size": output_info.uncompressed_rom_data_size,
+            "rom_size": output_info.rom_data_size,
+            "bank": match output_info.rom_bank {
+                Some(RomBank::Fixed(bank)) => serde_json::json!(bank),
+                Some(RomBank::Autobank) => serde_json::json!("autobank"),
+                None => serde_json::Value::Null,
+            },
+        })
+    }).collect();
+
+    return serde_json::to_string_pretty(&assets).unwrap();
+}
+
+fn rom_bank_string(rom_bank: Option<RomBank>) -> String {
+    match rom_bank {
+        Some(RomBank::Fixed(bank)) => bank.to_string(),
+        Some(RomBank::Autobank) => String::from("autobank"),
+        None => String::from("-"),
+    }
+}
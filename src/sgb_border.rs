@@ -0,0 +1,209 @@
+// This module converts a 256x224 image into a Super Game Boy border: 4bpp SNES tile data, a 32x28 tilemap of
+// SNES attribute words and up to three 16-color palettes (the SNES palettes 4-6). The color 0 of every palette is
+// transparent, so the center window, in which the Game Boy screen is shown, has to consist of transparent pixels.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use image::Rgb;
+
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+
+const SGB_BORDER_WIDTH_IN_TILES: u32 = 32;
+const SGB_BORDER_HEIGHT_IN_TILES: u32 = 28;
+
+// The Game Boy screen (160x144) in the center of the border, in pixels
+const SGB_BORDER_WINDOW_X: u32 = 48;
+const SGB_BORDER_WINDOW_Y: u32 = 40;
+const SGB_BORDER_WINDOW_WIDTH: u32 = 160;
+const SGB_BORDER_WINDOW_HEIGHT: u32 = 144;
+
+const SGB_BORDER_MAX_TILE_COUNT: usize = 256;
+const SGB_BORDER_PALETTE_COUNT: usize = 3;
+// The color 0 of every palette is transparent
+const SGB_BORDER_COLORS_PER_PALETTE: usize = 15;
+// The border uses the SNES palettes 4-6
+const SGB_BORDER_FIRST_PALETTE_NUMBER: u16 = 4;
+
+pub struct SgbBorder {
+    // 32 bytes per tile: the bitplanes 0 and 1 of all lines, followed by the bitplanes 2 and 3
+    pub tile_data: Vec<u8>,
+    pub tile_count: usize,
+    // Little endian attribute words: bits 0-9 tile number, 10-12 palette, 14 x-flip, 15 y-flip
+    pub tilemap: Vec<u8>,
+    // 16 little endian BGR555 colors per palette
+    pub palettes: Vec<u8>,
+}
+
+/// Converts an SGB border image. Pixels with an alpha value below 128 or the transparent color are transparent.
+/// Tiles are deduplicated including their flipped versions.
+pub fn sgb_border_from_path(path: &Path, transparent_color: Option<Rgb<u8>>) -> SgbBorder {
+    let image = image::open(path)
+        .expect(format!("Failed to open the path \"{}\"", path.imm_to_str()).as_str())
+        .into_rgba8();
+    if image.dimensions() != (SGB_BORDER_WIDTH_IN_TILES * 8, SGB_BORDER_HEIGHT_IN_TILES * 8) {
+        panic!(
+            "The SGB border \"{}\" is {}x{} pixels, but SGB borders have to be {}x{} pixels",
+            path.imm_to_str(), image.width(), image.height(), SGB_BORDER_WIDTH_IN_TILES * 8, SGB_BORDER_HEIGHT_IN_TILES * 8
+        );
+    }
+
+    // Colors are BGR555 words, transparent pixels are None
+    let pixel_color = |x: u32, y: u32| -> Option<u16> {
+        let pixel = image.get_pixel(x, y);
+        if pixel[3] < 128 || transparent_color == Some(Rgb([pixel[0], pixel[1], pixel[2]])) {
+            return None;
+        }
+        return Some(bgr555_from_rgb(Rgb([pixel[0], pixel[1], pixel[2]])));
+    };
+
+    for y in SGB_BORDER_WINDOW_Y..(SGB_BORDER_WINDOW_Y + SGB_BORDER_WINDOW_HEIGHT) {
+        for x in SGB_BORDER_WINDOW_X..(SGB_BORDER_WINDOW_X + SGB_BORDER_WINDOW_WIDTH) {
+            if pixel_color(x, y).is_some() {
+                panic!(
+                    "The center window ({},{})-({},{}) of the SGB border \"{}\" shows the Game Boy screen and has to be transparent, but the pixel ({},{}) isn't",
+                    SGB_BORDER_WINDOW_X, SGB_BORDER_WINDOW_Y, SGB_BORDER_WINDOW_X + SGB_BORDER_WINDOW_WIDTH - 1, SGB_BORDER_WINDOW_Y + SGB_BORDER_WINDOW_HEIGHT - 1,
+                    path.imm_to_str(), x, y
+                );
+            }
+        }
+    }
+
+    let tile_positions: Vec<(u32, u32)> = (0..SGB_BORDER_HEIGHT_IN_TILES)
+        .flat_map(|tile_y| (0..SGB_BORDER_WIDTH_IN_TILES).map(move |tile_x| (tile_x, tile_y)))
+        .collect();
+    let tile_colors = |(tile_x, tile_y): (u32, u32)| -> Vec<u16> {
+        let mut res: Vec<u16> = (0..64).filter_map(|i| pixel_color(tile_x * 8 + i % 8, tile_y * 8 + i / 8)).collect();
+        res.sort();
+        res.dedup();
+        return res;
+    };
+
+    // Tiles with many colors are the hardest to fit into a palette, so they are assigned first
+    let mut palettes: Vec<Vec<u16>> = Vec::new();
+    let mut tile_palette_numbers: HashMap<(u32, u32), usize> = HashMap::new();
+    let mut sorted_tile_positions = tile_positions.clone();
+    sorted_tile_positions.sort_by_key(|x| std::cmp::Reverse(tile_colors(*x).len()));
+    for tile_position in sorted_tile_positions {
+        let colors = tile_colors(tile_position);
+        if colors.len() > SGB_BORDER_COLORS_PER_PALETTE {
+            panic!(
+                "The tile ({},{}) of the SGB border \"{}\" contains {} colors, but SGB border tiles can only contain {} colors apart from transparency",
+                tile_position.0, tile_position.1, path.imm_to_str(), colors.len(), SGB_BORDER_COLORS_PER_PALETTE
+            );
+        }
+
+        // The palette that needs the fewest additional colors
+        let best_palette = palettes.iter().enumerate()
+            .map(|(palette_number, palette)| (palette_number, colors.iter().filter(|x| !palette.contains(x)).count()))
+            .filter(|(palette_number, new_color_count)| palettes[*palette_number].len() + new_color_count <= SGB_BORDER_COLORS_PER_PALETTE)
+            .min_by_key(|(_, new_color_count)| *new_color_count);
+        let palette_number = match best_palette {
+            Some((palette_number, _)) => palette_number,
+            None if palettes.len() < SGB_BORDER_PALETTE_COUNT => {
+                palettes.push(Vec::new());
+                palettes.len() - 1
+            },
+            None => panic!(
+                "The colors of the SGB border \"{}\" don't fit into {} palettes of {} colors (the tile ({},{}) doesn't fit into any palette)",
+                path.imm_to_str(), SGB_BORDER_PALETTE_COUNT, SGB_BORDER_COLORS_PER_PALETTE, tile_position.0, tile_position.1
+            ),
+        };
+        for color in colors {
+            if !palettes[palette_number].contains(&color) {
+                palettes[palette_number].push(color);
+            }
+        }
+        tile_palette_numbers.insert(tile_position, palette_number);
+    }
+    if palettes.is_empty() {
+        palettes.push(Vec::new());
+    }
+
+    // Every version of a tile (original and flipped) and the tile number and flips of the tile it belongs to
+    let mut tile_searchmap: HashMap<Array2d<u8, 8, 8>, (u16, bool, bool)> = HashMap::new();
+    let mut tiles: Vec<Array2d<u8, 8, 8>> = Vec::new();
+    let mut tilemap = Vec::new();
+    for tile_position in tile_positions {
+        let palette_number = tile_palette_numbers[&tile_position];
+        let mut tile: Array2d<u8, 8, 8> = Array2d::new();
+        for x in 0..8 {
+            for y in 0..8 {
+                // The color 0 is transparent, the palette colors start at 1
+                let color_index = match pixel_color(tile_position.0 * 8 + x, tile_position.1 * 8 + y) {
+                    Some(color) => palettes[palette_number].iter().position(|x| *x == color).unwrap() as u8 + 1,
+                    None => 0,
+                };
+                tile.assign(x, y, color_index);
+            }
+        }
+
+        let (tile_number, x_flip, y_flip) = match tile_searchmap.get(&tile) {
+            Some(value) => *value,
+            None => {
+                let tile_number = tiles.len() as u16;
+                // The unflipped version is inserted first, so that symmetric tiles are preferably used unflipped
+                for (x_flip, y_flip) in [(false, false), (true, false), (false, true), (true, true)] {
+                    let mut flipped_tile: Array2d<u8, 8, 8> = Array2d::new();
+                    for x in 0..8 {
+                        for y in 0..8 {
+                            flipped_tile.assign(if x_flip { 7 - x } else { x }, if y_flip { 7 - y } else { y }, tile.get(x, y));
+                        }
+                    }
+                    tile_searchmap.entry(flipped_tile).or_insert((tile_number, x_flip, y_flip));
+                }
+                tiles.push(tile);
+                (tile_number, false, false)
+            },
+        };
+
+        let attribute_word = tile_number
+            | ((SGB_BORDER_FIRST_PALETTE_NUMBER + palette_number as u16) << 10)
+            | ((x_flip as u16) << 14)
+            | ((y_flip as u16) << 15);
+        tilemap.extend(attribute_word.to_le_bytes());
+    }
+
+    if tiles.len() > SGB_BORDER_MAX_TILE_COUNT {
+        panic!(
+            "The SGB border \"{}\" contains {} distinct tiles (including flipped versions), but SGB borders can only contain {} tiles",
+            path.imm_to_str(), tiles.len(), SGB_BORDER_MAX_TILE_COUNT
+        );
+    }
+
+    let mut tile_data = Vec::new();
+    for tile in tiles.iter() {
+        for bitplane_pair in [0, 2] {
+            for y in 0..8 {
+                for bitplane in [bitplane_pair, bitplane_pair + 1] {
+                    tile_data.push((0..8).fold(0, |byte, x| (byte << 1) | ((tile.get(x, y) >> bitplane) & 1)));
+                }
+            }
+        }
+    }
+
+    let mut palette_bytes = Vec::new();
+    for palette in palettes.iter() {
+        for color_index in 0..=SGB_BORDER_COLORS_PER_PALETTE {
+            let color = match color_index {
+                0 => 0,
+                _ => *palette.get(color_index - 1).unwrap_or(&0),
+            };
+            palette_bytes.extend(color.to_le_bytes());
+        }
+    }
+
+    return SgbBorder {
+        tile_data: tile_data,
+        tile_count: tiles.len(),
+        tilemap: tilemap,
+        palettes: palette_bytes,
+    };
+}
+
+/// Converts a color into the SNES color format (5 bits per channel, red in the lowest bits)
+pub fn bgr555_from_rgb(color: Rgb<u8>) -> u16 {
+    let channel = |value: u8| (value as u16 * 31 + 127) / 255;
+    return channel(color[0]) | (channel(color[1]) << 5) | (channel(color[2]) << 10);
+}
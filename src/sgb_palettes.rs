@@ -0,0 +1,262 @@
+// This module colorizes a DMG screen on the Super Game Boy. A colored version of the screen tells which color
+// every palette index of the DMG map should be displayed with. Up to four SGB palettes are derived from it and
+// assigned to the tiles of the screen, and both are encoded into the command packets of the SGB: PAL01/PAL23
+// for the palettes and ATTR_BLK (rectangles) or ATTR_CHR (every tile) for the palette assignment.
+
+use std::path::Path;
+
+use image::Rgb;
+
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+use crate::read_aseprite::*;
+use crate::read_input::*;
+use crate::sgb_border::*;
+
+const SGB_SCREEN_WIDTH_IN_TILES: usize = 20;
+const SGB_SCREEN_HEIGHT_IN_TILES: usize = 18;
+const SGB_PALETTE_COUNT: usize = 4;
+const SGB_PACKET_SIZE: usize = 16;
+
+const SGB_COMMAND_PAL01: u8 = 0x00;
+const SGB_COMMAND_PAL23: u8 = 0x01;
+const SGB_COMMAND_ATTR_BLK: u8 = 0x04;
+const SGB_COMMAND_ATTR_CHR: u8 = 0x07;
+const SGB_ATTR_BLK_MAX_DATA_SET_COUNT: usize = 18;
+
+// Palette indices that no tile of a palette uses receive the default DMG shades
+const SGB_DEFAULT_SHADES: [Rgb<u8>; 4] = [Rgb([255, 255, 255]), Rgb([170, 170, 170]), Rgb([85, 85, 85]), Rgb([0, 0, 0])];
+
+pub struct SgbColorization {
+    // The four BGR555 colors of every palette, the color 0 is shared by all palettes
+    pub palettes: Vec<[u16; 4]>,
+    pub tile_palette_numbers: [[u8; SGB_SCREEN_WIDTH_IN_TILES]; SGB_SCREEN_HEIGHT_IN_TILES],
+}
+
+/// Derives the SGB palettes and the palette of every tile from a DMG screen (160x144) and its colored version
+pub fn sgb_colorization_from_images(dmg_screen_image: &TileSourceImage, color_palette: &ColorPalette, dmg_screen_path: &Path, colored_screen_path: &Path, aseprite_layer_selection: &AsepriteLayerSelection) -> SgbColorization {
+    let colored_screen_image = rgbimage_from_path(colored_screen_path, aseprite_layer_selection);
+    for (image_path, dimensions) in [(dmg_screen_path, (dmg_screen_image.width(), dmg_screen_image.height())), (colored_screen_path, colored_screen_image.dimensions())] {
+        if dimensions != (SGB_SCREEN_WIDTH_IN_TILES as u32 * 8, SGB_SCREEN_HEIGHT_IN_TILES as u32 * 8) {
+            panic!(
+                "The SGB colorized screen \"{}\" is {}x{} pixels, but it has to be the size of the screen ({}x{} pixels)",
+                image_path.imm_to_str(), dimensions.0, dimensions.1, SGB_SCREEN_WIDTH_IN_TILES * 8, SGB_SCREEN_HEIGHT_IN_TILES * 8
+            );
+        }
+    }
+
+    // The color of every palette index within every tile
+    let mut tile_colors: Vec<((usize, usize), [Option<u16>; 4])> = Vec::new();
+    let mut color_0: Option<u16> = None;
+    for tile_y in 0..SGB_SCREEN_HEIGHT_IN_TILES {
+        for tile_x in 0..SGB_SCREEN_WIDTH_IN_TILES {
+            let palette_indices = read_tile_from_tile_source_image(tile_x as u32, tile_y as u32, dmg_screen_image, color_palette);
+            let mut colors: [Option<u16>; 4] = [None; 4];
+            for x in 0..8 {
+                for y in 0..8 {
+                    let palette_index = palette_indices.get(x, y) as usize;
+                    let color = bgr555_from_rgb(*colored_screen_image.get_pixel(tile_x as u32 * 8 + x as u32, tile_y as u32 * 8 + y as u32));
+                    if colors[palette_index].is_some_and(|x| x != color) {
+                        panic!(
+                            "The pixels of the palette index {} of the tile ({},{}) of \"{}\" have different colors in \"{}\", but a tile can only use a single SGB palette",
+                            palette_index, tile_x, tile_y, dmg_screen_path.imm_to_str(), colored_screen_path.imm_to_str()
+                        );
+                    }
+                    colors[palette_index] = Some(color);
+                }
+            }
+            if let Some(tile_color_0) = colors[0] {
+                if color_0.is_some_and(|x| x != tile_color_0) {
+                    panic!(
+                        "The pixels of the palette index 0 have different colors in \"{}\" (e.g. in the tile ({},{})), but the color 0 is shared by all SGB palettes",
+                        colored_screen_path.imm_to_str(), tile_x, tile_y
+                    );
+                }
+                color_0 = Some(tile_color_0);
+            }
+            tile_colors.push(((tile_x, tile_y), colors));
+        }
+    }
+
+    // Tiles that specify the most colors are the hardest to fit into a palette, so they are assigned first
+    let mut palettes: Vec<[Option<u16>; 4]> = Vec::new();
+    let mut tile_palette_numbers = [[0; SGB_SCREEN_WIDTH_IN_TILES]; SGB_SCREEN_HEIGHT_IN_TILES];
+    tile_colors.sort_by_key(|(_, colors)| std::cmp::Reverse(colors[1..].iter().filter(|x| x.is_some()).count()));
+    for ((tile_x, tile_y), colors) in tile_colors {
+        let is_compatible = |palette: &[Option<u16>; 4]| (1..4).all(|i| colors[i].is_none() || palette[i].is_none() || colors[i] == palette[i]);
+        let new_color_count = |palette: &[Option<u16>; 4]| (1..4).filter(|i| colors[*i].is_some() && palette[*i].is_none()).count();
+
+        // The compatible palette that needs the fewest additional colors
+        let best_palette = palettes.iter().enumerate()
+            .filter(|(_, palette)| is_compatible(palette))
+            .min_by_key(|(_, palette)| new_color_count(palette))
+            .map(|(palette_number, _)| palette_number);
+        let palette_number = match best_palette {
+            Some(palette_number) => palette_number,
+            None if palettes.len() < SGB_PALETTE_COUNT => {
+                palettes.push([None; 4]);
+                palettes.len() - 1
+            },
+            None => panic!(
+                "The colors of \"{}\" don't fit into {} SGB palettes (the tile ({},{}) doesn't fit into any palette)",
+                colored_screen_path.imm_to_str(), SGB_PALETTE_COUNT, tile_x, tile_y
+            ),
+        };
+        for i in 1..4 {
+            if colors[i].is_some() {
+                palettes[palette_number][i] = colors[i];
+            }
+        }
+        tile_palette_numbers[tile_y][tile_x] = palette_number as u8;
+    }
+
+    let color_0 = color_0.unwrap_or(bgr555_from_rgb(SGB_DEFAULT_SHADES[0]));
+    return SgbColorization {
+        palettes: palettes.iter()
+            .map(|palette| std::array::from_fn(|i| match i {
+                0 => color_0,
+                _ => palette[i].unwrap_or(bgr555_from_rgb(SGB_DEFAULT_SHADES[i])),
+            }))
+            .collect(),
+        tile_palette_numbers: tile_palette_numbers,
+    };
+}
+
+/// Returns the PAL01 packet, followed by the PAL23 packet if more than two palettes are used
+pub fn sgb_palette_packets(sgb_colorization: &SgbColorization) -> Vec<u8> {
+    let mut res = Vec::new();
+    for (command, first_palette_number) in [(SGB_COMMAND_PAL01, 0), (SGB_COMMAND_PAL23, 2)] {
+        if first_palette_number >= sgb_colorization.palettes.len() {
+            break;
+        }
+        let palette = |palette_number: usize| sgb_colorization.palettes.get(palette_number).unwrap_or(&sgb_colorization.palettes[0]);
+
+        // The color 0 is only sent once, followed by the colors 1-3 of both palettes
+        let mut packet = vec![(command << 3) | 1];
+        packet.extend(palette(first_palette_number)[0].to_le_bytes());
+        for palette_number in [first_palette_number, first_palette_number + 1] {
+            for i in 1..4 {
+                packet.extend(palette(palette_number)[i].to_le_bytes());
+            }
+        }
+        packet.resize(SGB_PACKET_SIZE, 0);
+        res.extend(packet);
+    }
+    return res;
+}
+
+/// Returns the packets that assign the palettes to the tiles of the screen. ATTR_BLK packets describe the assignment
+/// with rectangles, which is usually shorter. If it needs too many rectangles, ATTR_CHR packets assign a palette
+/// to every tile. The returned bool tells whether ATTR_BLK packets are used.
+pub fn sgb_attribute_packets(sgb_colorization: &SgbColorization) -> (Vec<u8>, bool) {
+    let tile_palette_numbers = &sgb_colorization.tile_palette_numbers;
+
+    // The whole screen receives the most common palette, rectangles of tiles with other palettes are drawn over it
+    let mut palette_tile_counts = [0; SGB_PALETTE_COUNT];
+    for palette_number in tile_palette_numbers.iter().flatten() {
+        palette_tile_counts[*palette_number as usize] += 1;
+    }
+    let background_palette_number = (0..SGB_PALETTE_COUNT).max_by_key(|x| (palette_tile_counts[*x], std::cmp::Reverse(*x))).unwrap() as u8;
+    let mut rectangles = vec![(background_palette_number, 0, 0, SGB_SCREEN_WIDTH_IN_TILES - 1, SGB_SCREEN_HEIGHT_IN_TILES - 1)];
+    let mut is_covered = [[false; SGB_SCREEN_WIDTH_IN_TILES]; SGB_SCREEN_HEIGHT_IN_TILES];
+    for tile_y in 0..SGB_SCREEN_HEIGHT_IN_TILES {
+        for tile_x in 0..SGB_SCREEN_WIDTH_IN_TILES {
+            let palette_number = tile_palette_numbers[tile_y][tile_x];
+            if palette_number == background_palette_number || is_covered[tile_y][tile_x] {
+                continue;
+            }
+            // Grow the rectangle to the right first and then downwards
+            let is_extendable = |x: usize, y: usize| tile_palette_numbers[y][x] == palette_number && !is_covered[y][x];
+            let mut last_x = tile_x;
+            while last_x + 1 < SGB_SCREEN_WIDTH_IN_TILES && is_extendable(last_x + 1, tile_y) {
+                last_x += 1;
+            }
+            let mut last_y = tile_y;
+            while last_y + 1 < SGB_SCREEN_HEIGHT_IN_TILES && (tile_x..=last_x).all(|x| is_extendable(x, last_y + 1)) {
+                last_y += 1;
+            }
+            for y in tile_y..=last_y {
+                for x in tile_x..=last_x {
+                    is_covered[y][x] = true;
+                }
+            }
+            rectangles.push((palette_number, tile_x, tile_y, last_x, last_y));
+        }
+    }
+
+    let attr_blk_packet_count = (2 + 6 * rectangles.len()).div_ceil(SGB_PACKET_SIZE);
+    let attr_chr_packet_count = (6 + (SGB_SCREEN_WIDTH_IN_TILES * SGB_SCREEN_HEIGHT_IN_TILES).div_ceil(4)).div_ceil(SGB_PACKET_SIZE);
+
+    let mut res;
+    let use_attr_blk = rectangles.len() <= SGB_ATTR_BLK_MAX_DATA_SET_COUNT && attr_blk_packet_count <= attr_chr_packet_count;
+    if use_attr_blk {
+        res = vec![(SGB_COMMAND_ATTR_BLK << 3) | attr_blk_packet_count as u8, rectangles.len() as u8];
+        for (palette_number, first_x, first_y, last_x, last_y) in rectangles {
+            // The inside and the border line of the rectangle receive the palette
+            res.extend([0b011, palette_number | (palette_number << 2), first_x as u8, first_y as u8, last_x as u8, last_y as u8]);
+        }
+        res.resize(attr_blk_packet_count * SGB_PACKET_SIZE, 0);
+    } else {
+        let tile_count = SGB_SCREEN_WIDTH_IN_TILES * SGB_SCREEN_HEIGHT_IN_TILES;
+        // Start at the tile (0,0) and write the palettes from left to right, 4 tiles per byte
+        res = vec![(SGB_COMMAND_ATTR_CHR << 3) | attr_chr_packet_count as u8, 0, 0];
+        res.extend((tile_count as u16).to_le_bytes());
+        res.push(0);
+        for tile_palette_numbers in tile_palette_numbers.iter().flatten().collect::<Vec<&u8>>().chunks(4) {
+            res.push(tile_palette_numbers.iter().enumerate().fold(0, |byte, (i, palette_number)| byte | (**palette_number << (6 - 2 * i))));
+        }
+        res.resize(attr_chr_packet_count * SGB_PACKET_SIZE, 0);
+    }
+
+    return (res, use_attr_blk);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sgb_colorization(palettes: Vec<[u16; 4]>, tile_palette_number: impl Fn(usize, usize) -> u8) -> SgbColorization {
+        return SgbColorization {
+            palettes: palettes,
+            tile_palette_numbers: std::array::from_fn(|y| std::array::from_fn(|x| tile_palette_number(x, y))),
+        };
+    }
+
+    #[test]
+    fn pal01_packet() {
+        let sgb_colorization = sgb_colorization(vec![[0x7FFF, 0x001F, 0x03E0, 0x7C00], [0x7FFF, 0x1234, 0x0000, 0x4321]], |_, _| 0);
+        assert_eq!(sgb_palette_packets(&sgb_colorization), vec![
+            0x01, 0xFF, 0x7F, 0x1F, 0x00, 0xE0, 0x03, 0x00, 0x7C, 0x34, 0x12, 0x00, 0x00, 0x21, 0x43, 0x00,
+        ]);
+    }
+
+    #[test]
+    fn pal23_packet_repeats_the_first_palette() {
+        let sgb_colorization = sgb_colorization(vec![[0x7FFF, 0x0001, 0x0002, 0x0003], [0x7FFF, 0x0004, 0x0005, 0x0006], [0x7FFF, 0x0007, 0x0008, 0x0009]], |_, _| 0);
+        assert_eq!(sgb_palette_packets(&sgb_colorization), vec![
+            0x01, 0xFF, 0x7F, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x04, 0x00, 0x05, 0x00, 0x06, 0x00, 0x00,
+            0x09, 0xFF, 0x7F, 0x07, 0x00, 0x08, 0x00, 0x09, 0x00, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x00,
+        ]);
+    }
+
+    #[test]
+    fn attr_blk_packet() {
+        // The tiles (2,3) to (4,5) use the palette 1, all other tiles the palette 0
+        let sgb_colorization = sgb_colorization(vec![[0x7FFF; 4], [0x0000; 4]], |x, y| ((2..=4).contains(&x) && (3..=5).contains(&y)) as u8);
+        assert_eq!(sgb_attribute_packets(&sgb_colorization), (vec![
+            0x21, 0x02, 0x03, 0x00, 0x00, 0x00, 0x13, 0x11, 0x03, 0x05, 0x02, 0x03, 0x04, 0x05, 0x00, 0x00,
+        ], true));
+    }
+
+    #[test]
+    fn attr_chr_packets() {
+        // A checkerboard needs a rectangle for every tile of the palette 1
+        let sgb_colorization = sgb_colorization(vec![[0x7FFF; 4], [0x0000; 4]], |x, y| ((x + y) % 2) as u8);
+        let mut expected_packets = vec![0x3E, 0x00, 0x00, 0x68, 0x01, 0x00];
+        for y in 0..SGB_SCREEN_HEIGHT_IN_TILES {
+            expected_packets.extend([if y % 2 == 0 { 0x11 } else { 0x44 }; SGB_SCREEN_WIDTH_IN_TILES / 4]);
+        }
+        assert_eq!(expected_packets.len(), 6 * SGB_PACKET_SIZE);
+        assert_eq!(sgb_attribute_packets(&sgb_colorization), (expected_packets, false));
+    }
+}
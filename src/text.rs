@@ -0,0 +1,168 @@
+// This module encodes game texts (e.g. dialog strings) into byte strings of tile indices of the font tileset,
+// so that the texts can be copied to the tilemap directly. Control codes, which don't collide with the tile
+// indices of the font, mark line breaks, pauses and the end of a text.
+
+use std::path::Path;
+
+use crate::helper::*;
+
+pub const TEXT_CONTROL_CODE_PAUSE: u8 = 0xFD;
+pub const TEXT_CONTROL_CODE_NEWLINE: u8 = 0xFE;
+pub const TEXT_CONTROL_CODE_END: u8 = 0xFF;
+
+pub struct GameText {
+    pub name: String,
+    pub text: String,
+}
+
+/// Reads the named texts of a text file. The format depends on the extension:
+///  .json: an object whose members are the texts
+///  .csv: rows of the form name,text (fields with commas or quotes are quoted, "" is a quote inside a quoted field),
+///        an optional header row name,text is skipped
+///  otherwise: lines of the form "name: text", empty lines and lines starting with # are ignored
+pub fn game_texts_from_path(path: &Path) -> Vec<GameText> {
+    let file_content = std::fs::read_to_string(path)
+        .expect(format!("Couldn't read the text file: {}", path.imm_to_str()).as_str());
+
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("json") => {
+            let json: serde_json::Value = serde_json::from_str(file_content.as_str())
+                .unwrap_or_else(|x| panic!("The text file \"{}\" isn't valid JSON: {}", path.imm_to_str(), x));
+            let Some(members) = json.as_object() else {
+                panic!("The text file \"{}\" should contain an object, whose members are the texts", path.imm_to_str());
+            };
+            members.iter().map(|(name, text)| GameText {
+                name: name.clone(),
+                text: text.as_str().unwrap_or_else(|| panic!("The member \"{}\" of the text file \"{}\" isn't a string", name, path.imm_to_str())).to_string(),
+            }).collect()
+        },
+        Some("csv") => {
+            let mut res = Vec::new();
+            for (row_index, row) in csv_rows(file_content.as_str()).into_iter().enumerate() {
+                if row_index == 0 && row.len() == 2 && row[0] == "name" && row[1] == "text" {
+                    continue;
+                }
+                if row.len() != 2 {
+                    panic!("Row {} of the text file \"{}\" should have the form name,text", row_index + 1, path.imm_to_str());
+                }
+                res.push(GameText {
+                    name: row[0].clone(),
+                    text: row[1].clone(),
+                });
+            }
+            res
+        },
+        _ => {
+            let mut res = Vec::new();
+            for (line_index, line) in file_content.lines().enumerate() {
+                if line.trim().is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let Some((name, text)) = line.split_once(':') else {
+                    panic!("Line {} of the text file \"{}\" should have the form \"name: text\"", line_index + 1, path.imm_to_str());
+                };
+                res.push(GameText {
+                    name: name.trim().to_string(),
+                    // Only the space after the colon is removed, so that texts can start with spaces
+                    text: text.strip_prefix(' ').unwrap_or(text).to_string(),
+                });
+            }
+            res
+        },
+    }
+}
+
+/// Encodes a text into tile indices of the font. Line breaks and the escape sequence \n become newline
+/// control codes, \p becomes a pause control code and \\ a backslash. The end control code is appended.
+pub fn encode_game_text(game_text: &GameText, character_tile_indices: &Vec<(char, u8)>, text_file_path: &Path) -> Vec<u8> {
+    let mut res = Vec::new();
+    let mut characters = game_text.text.chars();
+
+    while let Some(character) = characters.next() {
+        let character = match character {
+            '\n' => {
+                res.push(TEXT_CONTROL_CODE_NEWLINE);
+                continue;
+            },
+            '\r' => continue,
+            '\\' => match characters.next() {
+                Some('n') => {
+                    res.push(TEXT_CONTROL_CODE_NEWLINE);
+                    continue;
+                },
+                Some('p') => {
+                    res.push(TEXT_CONTROL_CODE_PAUSE);
+                    continue;
+                },
+                Some('\\') => '\\',
+                escaped_character => panic!(
+                    "The text \"{}\" of the text file \"{}\" contains the unknown escape sequence \\{} (known escape sequences are \\n, \\p and \\\\)",
+                    game_text.name, text_file_path.imm_to_str(), escaped_character.map(|x| x.to_string()).unwrap_or_default()
+                ),
+            },
+            _ => character,
+        };
+
+        let Some((_, tile_index)) = character_tile_indices.iter().find(|(x, _)| *x == character) else {
+            panic!(
+                "The text \"{}\" of the text file \"{}\" contains the character '{}', which doesn't have a glyph in the font",
+                game_text.name, text_file_path.imm_to_str(), character
+            );
+        };
+        res.push(*tile_index);
+    }
+    res.push(TEXT_CONTROL_CODE_END);
+
+    return res;
+}
+
+/// Checks that no glyph of the font has the tile index of a control code
+pub fn check_font_for_text_control_codes(character_tile_indices: &Vec<(char, u8)>) {
+    for (character, tile_index) in character_tile_indices.iter() {
+        if *tile_index >= TEXT_CONTROL_CODE_PAUSE {
+            panic!(
+                "The glyph of the character '{}' has the tile index {:#04x}, but the tile indices {:#04x}-{:#04x} are reserved for the control codes of texts",
+                character, tile_index, TEXT_CONTROL_CODE_PAUSE, TEXT_CONTROL_CODE_END
+            );
+        }
+    }
+}
+
+// Splits CSV content into rows of fields
+fn csv_rows(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut characters = content.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        match (in_quotes, character) {
+            (true, '"') => {
+                if characters.peek() == Some(&'"') {
+                    characters.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            },
+            (true, _) => field.push(character),
+            (false, '"') => in_quotes = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') => {},
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            },
+            (false, _) => field.push(character),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    // Empty lines don't contain texts
+    rows.retain(|x| !(x.len() == 1 && x[0].is_empty()));
+    return rows;
+}
@@ -0,0 +1,88 @@
+// This module handles the properties (e.g. solid, damage, animated) of the tiles of the reference tileset.
+// Tiles receive properties from the subdirectories of a reference tileset directory and from a tile properties
+// file. Every property becomes a bit of a per-tile property byte, so that e.g. collision can be looked up from
+// the tile index directly.
+
+use std::path::Path;
+
+use crate::helper::*;
+use crate::input_data_representation_types::*;
+
+// Properties that always receive the same bits, custom properties receive the following bits in alphabetical order
+const PREDEFINED_TILE_PROPERTIES: [&str; 3] = ["solid", "damage", "animated"];
+
+/// Reads a tile properties file and adds its properties to the tiles. Every line of the file has the form
+/// "<tile name or tile index>: <property> <property> ...", empty lines and lines starting with # are ignored.
+pub fn apply_tile_properties_file(tile_properties_file_path: &Path, tile_info_vec: &mut Vec<TileInfo>) {
+    let file_content = std::fs::read_to_string(tile_properties_file_path)
+        .expect(format!("Couldn't read tile properties file: {}", tile_properties_file_path.imm_to_str()).as_str());
+
+    for (line_index, line) in file_content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((tile_str, properties_str)) = line.split_once(':') else {
+            panic!(
+                "Line {} of the tile properties file \"{}\" should have the form \"<tile name or tile index>: <property> <property> ...\"",
+                line_index + 1, tile_properties_file_path.imm_to_str()
+            );
+        };
+        let tile_str = tile_str.trim();
+
+        let tile_index = match tile_str.parse::<usize>() {
+            Ok(tile_index) => tile_index,
+            Err(_) => tile_info_vec.iter().position(|x| x.name.as_deref() == Some(tile_str)).unwrap_or_else(|| panic!(
+                "The tile properties file \"{}\" refers to the tile \"{}\" in line {}, but the reference tileset doesn't contain a tile with this name",
+                tile_properties_file_path.imm_to_str(), tile_str, line_index + 1
+            )),
+        };
+        if tile_index >= tile_info_vec.len() {
+            panic!(
+                "The tile properties file \"{}\" refers to the tile index {} in line {}, but the reference tileset contains only {} tiles",
+                tile_properties_file_path.imm_to_str(), tile_index, line_index + 1, tile_info_vec.len()
+            );
+        }
+
+        for property in properties_str.split_whitespace() {
+            if !tile_info_vec[tile_index].properties.iter().any(|x| x == property) {
+                tile_info_vec[tile_index].properties.push(property.to_string());
+            }
+        }
+    }
+}
+
+/// Returns the properties that are used by the tiles together with their bits. The predefined properties
+/// keep their bits even if they aren't used.
+pub fn tile_property_bits(tile_info_vec: &Vec<TileInfo>) -> Vec<(String, u8)> {
+    let mut custom_properties: Vec<&String> = tile_info_vec.iter()
+        .flat_map(|x| x.properties.iter())
+        .filter(|x| !PREDEFINED_TILE_PROPERTIES.contains(&x.as_str()))
+        .collect();
+    custom_properties.sort();
+    custom_properties.dedup();
+
+    if PREDEFINED_TILE_PROPERTIES.len() + custom_properties.len() > 8 {
+        panic!(
+            "The tiles use more than {} custom properties ({}), but every property needs a bit of the property byte",
+            8 - PREDEFINED_TILE_PROPERTIES.len(),
+            custom_properties.iter().map(|x| x.as_str()).collect::<Vec<&str>>().join(", ")
+        );
+    }
+
+    return PREDEFINED_TILE_PROPERTIES.iter().map(|x| x.to_string())
+        .chain(custom_properties.into_iter().cloned())
+        .enumerate()
+        .map(|(bit_index, property)| (property, 1u8 << bit_index))
+        .collect();
+}
+
+/// Returns the property byte of every tile, indexed by tile index
+pub fn tile_property_bytes(tile_info_vec: &Vec<TileInfo>, tile_property_bits: &Vec<(String, u8)>) -> Vec<u8> {
+    tile_info_vec.iter().map(|tile_info| {
+        tile_property_bits.iter()
+            .filter(|(property, _)| tile_info.properties.contains(property))
+            .fold(0, |byte, (_, bit)| byte | bit)
+    }).collect()
+}